@@ -0,0 +1,130 @@
+use std::path::{Path, PathBuf};
+use crate::error::Error;
+use crate::loader;
+use crate::loader::RunResult;
+use crate::sink;
+
+
+// Summarises the results below a directory in CSV files that lend themselves to be loaded into
+// spreadsheets:
+//   _all.csv          one row per run with its status and the main parameters
+//   _population.csv   the population size over time of the longest runs, one column per run
+//   _top40.txt        the result files of the runs in _population.csv, in column order
+// When per_run is set, a CSV file with the population and instruction counts over time is
+// written next to each result file.
+
+const TOP_RUNS: usize = 40;
+
+
+pub fn run(dir: &str, per_run: bool) -> Result<(), Error> {
+    let mut runs = Vec::new();
+    for path in loader::find_results(dir)? {
+        let path_str = path.to_str().ok_or_else(|| Error::InvalidPath(format!("{:?}", path)))?;
+        runs.push((path.clone(), loader::load_result(path_str)?));
+    }
+    println!("Analysing {} runs in {}.", runs.len(), dir);
+
+    loader::write_file(dir, "_all.csv", &summary_csv(&runs))?;
+    // longest runs first; sorting by path as well keeps the columns stable
+    runs.sort_by(|(p0, r0), (p1, r1)| r1.log.entries.len().cmp(&r0.log.entries.len()).then(p0.cmp(p1)));
+    let top = &runs[..runs.len().min(TOP_RUNS)];
+    loader::write_file(dir, "_population.csv", &population_csv(top))?;
+    let top_files: Vec<String> = top.iter().map(|(p, _)| format!("{}\n", p.display())).collect();
+    loader::write_file(dir, "_top40.txt", &top_files.concat())?;
+
+    if per_run {
+        // runs that were logged with the csv sink have their CSV file already
+        for (path, run) in runs.iter().filter(|(_, r)| !r.log_file.as_ref().is_some_and(|f| f.ends_with(".csv"))) {
+            let parent = path.parent().unwrap_or_else(|| Path::new("."));
+            let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(&run.id);
+            loader::write_file(&parent.to_string_lossy(), &format!("{}.csv", stem), &run_csv(run))?;
+        }
+    }
+    Ok(())
+}
+
+
+fn summary_csv(runs: &[(PathBuf, RunResult)]) -> String {
+    let header = ["id", "status", "cycles", "world_size", "world_end", "start_plant_count",
+        "start_population", "plant_start_ep", "creature_start_ep", "creature_max_ep",
+        "creature_max_age", "eat_ep", "view_distance", "prog_ring_count", "prog_ring_size"];
+    let mut lines = vec![csv_line(header.iter().map(|h| quote(h)))];
+    for (_, run) in runs {
+        let p = &run.params;
+        let numbers = [run.cycles, p.world_size as u64, p.world_end, p.start_plant_count as u64,
+            p.start_pop_size as u64, p.plant_start_ep as u64, p.creature_start_ep as u64,
+            p.creature_max_ep as u64, p.creature_max_age, p.eat_ep as u64, p.view_distance as u64,
+            p.ring_count as u64, p.ring_size as u64];
+        let fields = vec![quote(&run.id), quote(&run.status)].into_iter()
+            .chain(numbers.iter().map(|n| n.to_string()));
+        lines.push(csv_line(fields));
+    }
+    lines.concat()
+}
+
+fn population_csv(runs: &[(PathBuf, RunResult)]) -> String {
+    let header = (1..=runs.len()).map(|i| format!("f{}", i));
+    let mut lines = vec![csv_line(std::iter::once("cycle".to_owned()).chain(header))];
+    if let Some((_, longest)) = runs.first() {
+        for (i, entry) in longest.log.entries.iter().enumerate() {
+            let values = runs.iter().map(|(_, run)|
+                run.log.entries.get(i).and_then(|e| e.num_creatures()).map_or(String::new(), |n| n.to_string()));
+            lines.push(csv_line(std::iter::once(entry.cycle().to_string()).chain(values)));
+        }
+    }
+    lines.concat()
+}
+
+fn run_csv(run: &RunResult) -> String {
+    let lines: Vec<String> = run.log.entries.iter().map(sink::csv_line).collect();
+    sink::csv_header() + &lines.concat()
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+fn csv_line<I: Iterator<Item = String>>(fields: I) -> String {
+    let mut line = fields.collect::<Vec<String>>().join(",");
+    line.push('\n');
+    line
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+    use crate::log::Log;
+    use crate::params::Params;
+
+    fn run_log(id: &str, populations: &[u32]) -> (PathBuf, RunResult<'static>) {
+        let mut log = Log::new();
+        for (i, n) in populations.iter().enumerate() {
+            log.add_entry(10_000 + i as u64 * 1000);
+            log.set_num_creatures(*n);
+        }
+        let run = RunResult { format_version: loader::FORMAT_VERSION, id: id.to_owned(),
+            params: Params::for_testing(), seed: [1, 2, 3, 4], cycles: 12_000, status: "ENDOK".to_owned(),
+            sim_num: None, sweep_point: None, log_file: None, genealogy_file: None, log: Cow::Owned(log) };
+        (PathBuf::from(format!("log-{}.json", id)), run)
+    }
+
+    #[test]
+    fn summary_has_one_row_per_run() {
+        let csv = summary_csv(&[run_log("a", &[1]), run_log("b", &[2])]);
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(3, lines.len());
+        assert!(lines[0].starts_with("\"id\",\"status\",\"cycles\","));
+        assert!(lines[2].starts_with("\"b\",\"ENDOK\",12000,"));
+        assert_eq!(15, lines[2].split(',').count());
+    }
+
+    #[test]
+    fn population_has_one_column_per_run_and_leaves_missing_values_empty() {
+        let csv = population_csv(&[run_log("a", &[10, 11, 12]), run_log("b", &[20, 21])]);
+
+        assert_eq!("cycle,f1,f2\n10000,10,20\n11000,11,21\n12000,12,\n", csv);
+    }
+}
@@ -0,0 +1,417 @@
+use std::collections::BTreeMap;
+use serde_derive::*;
+use serde_json::Value;
+use uuid::Builder;
+use crate::error::Error;
+use crate::loader::Output;
+use crate::params::Params;
+use crate::random::RNG;
+use crate::world::World;
+
+
+#[derive(Serialize, Deserialize)]
+pub struct Campaign {
+    pub name: String,
+    pub num_sims: u32,
+    pub num_threads: u32,
+    pub params: Params,
+    // for each listed parameter every world gets one of the values, chosen at random
+    #[serde(default)]
+    pub randomize: BTreeMap<String, Vec<Value>>,
+    // when present, num_sims is replaced by the number of sweep points times the replicates
+    #[serde(default)]
+    pub sweep: Option<Sweep>,
+    // all seeds, random choices and sweep samples are derived from this seed
+    #[serde(default)]
+    pub seed: Option<[u32; 4]>,
+    #[serde(default)]
+    pub output: Output,
+}
+
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Sweep {
+    Grid { axes: BTreeMap<String, Vec<Value>>, replicates: u32 },
+    LatinHypercube { axes: BTreeMap<String, Range>, samples: u32, replicates: u32 },
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Range {
+    pub min: f64,
+    pub max: f64,
+}
+
+// Lists every run of a campaign with its result file and the parameters that vary between runs,
+// so that tools don't have to read all result files. It also records which simulations have
+// finished when a campaign is interrupted.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Index {
+    pub seed: Option<[u32; 4]>,
+    pub runs: BTreeMap<u32, IndexEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IndexEntry {
+    pub id: String,
+    pub file: String,
+    pub seed: [u32; 4],
+    pub status: String,
+    pub cycles: u64,
+    pub params: BTreeMap<String, Value>,
+}
+
+pub type SweepValues = BTreeMap<String, Value>;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SweepPoint {
+    pub index: u32,
+    pub replicate: u32,
+    pub values: SweepValues,
+}
+
+
+impl Campaign {
+    pub fn check(&self) -> Result<(), Error> {
+        let mut problems = Vec::new();
+        if self.num_threads == 0 {
+            problems.push("num_threads must be greater than 0".to_owned());
+        }
+        let base = serde_json::to_value(&self.params).unwrap();
+        for (field, values) in &self.randomize {
+            check_values(&base, field, values, &mut problems);
+        }
+        match &self.sweep {
+            Some(Sweep::Grid { axes, replicates }) => {
+                for (field, values) in axes {
+                    check_values(&base, field, values, &mut problems);
+                }
+                if *replicates == 0 {
+                    problems.push("replicates must be greater than 0".to_owned());
+                }
+            }
+            Some(Sweep::LatinHypercube { axes, samples, replicates }) => {
+                for (field, range) in axes {
+                    if !base.get(field).is_some_and(|v| v.is_number()) {
+                        problems.push(format!("cannot sweep {}, which is not a numeric parameter", field));
+                    } else if range.min > range.max {
+                        problems.push(format!("range for {} has min greater than max", field));
+                    } else if base[field].is_u64() && integers_in(range).len() < *samples as usize {
+                        problems.push(format!("range for {} has fewer whole numbers than the {} samples", field, samples));
+                    }
+                }
+                if *samples == 0 || *replicates == 0 {
+                    problems.push("samples and replicates must be greater than 0".to_owned());
+                }
+            }
+            None => {}
+        }
+        if problems.is_empty() { Ok(()) } else { Err(Error::InvalidParams(problems)) }
+    }
+
+    pub fn sweep_points(&self) -> Vec<SweepValues> {
+        let mut rng = match self.seed {
+            Some(seed) => RNG::from_seed(&seed),
+            None => RNG::new(),
+        };
+        self.sweep_points_with_rng(&mut rng)
+    }
+
+    fn sweep_points_with_rng(&self, rng: &mut RNG) -> Vec<SweepValues> {
+        match &self.sweep {
+            Some(Sweep::Grid { axes, .. }) => grid_points(axes),
+            Some(Sweep::LatinHypercube { axes, samples, .. }) => {
+                let base = serde_json::to_value(&self.params).unwrap();
+                latin_hypercube_points(axes, *samples, &base, rng)
+            }
+            None => Vec::new(),
+        }
+    }
+
+    pub fn num_runs(&self, points: &[SweepValues]) -> u32 {
+        match &self.sweep {
+            Some(Sweep::Grid { replicates, .. }) | Some(Sweep::LatinHypercube { replicates, .. }) =>
+                points.len() as u32 * replicates,
+            None => self.num_sims,
+        }
+    }
+
+    pub fn sweep_point(&self, sim_num: u32, points: &[SweepValues]) -> Option<SweepPoint> {
+        match &self.sweep {
+            Some(Sweep::Grid { replicates, .. }) | Some(Sweep::LatinHypercube { replicates, .. }) => {
+                let index = sim_num / replicates;
+                Some(SweepPoint { index, replicate: sim_num % replicates, values: points[index as usize].clone() })
+            }
+            None => None,
+        }
+    }
+
+    pub fn make_params(&self, rng: &mut RNG, sweep_point: Option<&SweepPoint>) -> Result<Params, Error> {
+        let mut values = BTreeMap::new();
+        for (field, choices) in &self.randomize {
+            values.insert(field.clone(), rng.choose(choices));
+        }
+        if let Some(point) = sweep_point {
+            values.extend(point.values.clone());
+        }
+        self.params.with_values(&values)
+    }
+
+    // With a campaign seed, everything about a world depends only on its simulation number,
+    // which makes results independent of the number of threads and of thread scheduling.
+    pub fn make_world(&self, sim_num: u32, points: &[SweepValues]) -> Result<World, Error> {
+        let sim_seed = match self.seed {
+            Some(seed) => RNG::derive_seed(&seed, sim_num as u64),
+            None => RNG::new_seed(),
+        };
+        let mut rng = RNG::from_seed(&RNG::derive_seed(&sim_seed, 1));
+        let sweep_point = self.sweep_point(sim_num, points);
+        let mut world = World::new(&self.name, self.make_params(&mut rng, sweep_point.as_ref())?)?;
+        world.random = RNG::from_seed(&RNG::derive_seed(&sim_seed, 0));
+        world.id = id_from_seed(&RNG::derive_seed(&sim_seed, 2));
+        world.sim_num = Some(sim_num);
+        world.sweep_point = sweep_point;
+        world.output = self.output.clone();
+        Ok(world)
+    }
+
+    // The params that are randomized or swept, i.e. those that can differ between runs.
+    pub fn key_params(&self, params: &Params) -> BTreeMap<String, Value> {
+        let mut names: Vec<&String> = self.randomize.keys().collect();
+        match &self.sweep {
+            Some(Sweep::Grid { axes, .. }) => names.extend(axes.keys()),
+            Some(Sweep::LatinHypercube { axes, .. }) => names.extend(axes.keys()),
+            None => {}
+        }
+        let values = serde_json::to_value(params).unwrap();
+        names.into_iter().map(|n| (n.clone(), values[n].clone())).collect()
+    }
+
+    pub fn index_entry(&self, world: &World) -> IndexEntry {
+        IndexEntry {
+            id: world.id.clone(),
+            file: world.output.result_filename(world),
+            seed: *world.random.seed(),
+            status: world.status().to_owned(),
+            cycles: world.cycle,
+            params: self.key_params(&world.params),
+        }
+    }
+}
+
+
+impl Index {
+    pub fn is_finished(&self, sim_num: u32) -> bool {
+        self.runs.get(&sim_num).is_some_and(|r| r.status != "INTERRUPTED")
+    }
+
+    pub fn num_finished(&self) -> usize {
+        self.runs.keys().filter(|n| self.is_finished(**n)).count()
+    }
+}
+
+
+fn id_from_seed(seed: &[u32; 4]) -> String {
+    let mut bytes = [0u8; 16];
+    for (i, s) in seed.iter().enumerate() {
+        bytes[i * 4..i * 4 + 4].copy_from_slice(&s.to_be_bytes());
+    }
+    Builder::from_random_bytes(bytes).into_uuid().simple().to_string()
+}
+
+fn check_values(base: &Value, field: &str, values: &[Value], problems: &mut Vec<String>) {
+    if base.get(field).is_none() {
+        problems.push(format!("unknown parameter {}", field));
+    } else if values.is_empty() {
+        problems.push(format!("no values given for parameter {}", field));
+    }
+    for v in values {
+        let mut p = base.clone();
+        p[field] = v.clone();
+        if let Err(e) = serde_json::from_value::<Params>(p) {
+            problems.push(format!("invalid value {} for parameter {}: {}", v, field, e));
+        }
+    }
+}
+
+// All combinations of the values, with the last axis (in name order) varying fastest.
+
+fn grid_points(axes: &BTreeMap<String, Vec<Value>>) -> Vec<SweepValues> {
+    let mut points = vec![SweepValues::new()];
+    for (field, values) in axes {
+        points = points.iter().flat_map(|point| {
+            values.iter().map(move |v| {
+                let mut p = point.clone();
+                p.insert(field.clone(), v.clone());
+                p
+            })
+        }).collect();
+    }
+    points
+}
+
+// Each range is divided into n strata of equal width and every stratum is sampled exactly once.
+// The strata are combined across axes by independent random permutations. For integer params
+// the whole numbers in the range are divided into n strata instead, which must not be fewer
+// than n, so that no two strata yield the same value.
+
+fn latin_hypercube_points(axes: &BTreeMap<String, Range>, n: u32, base: &Value, rng: &mut RNG) -> Vec<SweepValues> {
+    let n = n as usize;
+    let mut points = vec![SweepValues::new(); n];
+    for (field, range) in axes {
+        let mut strata: Vec<usize> = (0..n).collect();
+        for i in (1..n).rev() {
+            strata.swap(i, rng.next_usize(i + 1));
+        }
+        let integers = if base[field].is_u64() { Some(integers_in(range)) } else { None };
+        for (point, stratum) in points.iter_mut().zip(strata) {
+            let v = match &integers {
+                Some(values) => {
+                    let (lo, hi) = (stratum * values.len() / n, (stratum + 1) * values.len() / n);
+                    Value::from(values[lo + rng.next_usize(hi - lo)])
+                }
+                None => Value::from(range.min + (range.max - range.min) * (stratum as f64 + rng.next_f64()) / n as f64),
+            };
+            point.insert(field.clone(), v);
+        }
+    }
+    points
+}
+
+fn integers_in(range: &Range) -> Vec<u64> {
+    let min = range.min.max(0.0).ceil() as u64;
+    let max = range.max.floor();
+    if max < min as f64 { Vec::new() } else { (min..=max as u64).collect() }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn campaign(randomize: BTreeMap<String, Vec<Value>>) -> Campaign {
+        Campaign {
+            name: "test".to_owned(), num_sims: 1, num_threads: 1, params: Params::for_testing(),
+            randomize, sweep: None, seed: None, output: Output::default()
+        }
+    }
+
+    #[test]
+    fn replaces_randomized_params() {
+        let mut randomize = BTreeMap::new();
+        randomize.insert("ring_size".to_owned(), vec![Value::from(5)]);
+        randomize.insert("plant_prob".to_owned(), vec![Value::from(0.5)]);
+        let c = campaign(randomize);
+
+        let p = c.make_params(&mut RNG::new(), None).unwrap();
+
+        assert_eq!(5, p.ring_size);
+        assert_eq!(0.5, p.plant_prob);
+        assert_eq!(Params::for_testing().ring_count, p.ring_count);
+    }
+
+    #[test]
+    fn example_campaign_is_valid() {
+        let c = crate::loader::load_campaign("resources/campaigns/ringstruct.json").unwrap();
+        assert!(c.check().is_ok());
+        assert!(c.params.validate().is_ok());
+    }
+
+    #[test]
+    fn check_reports_unknown_and_mistyped_params() {
+        let mut randomize = BTreeMap::new();
+        randomize.insert("ring_sizes".to_owned(), vec![Value::from(5)]);
+        randomize.insert("ring_count".to_owned(), vec![Value::from("two")]);
+        let c = campaign(randomize);
+
+        match c.check() {
+            Err(Error::InvalidParams(problems)) => assert_eq!(2, problems.len()),
+            _ => panic!("expected invalid params"),
+        }
+    }
+
+    #[test]
+    fn grid_sweep_enumerates_all_combinations_with_replicates() {
+        let mut axes = BTreeMap::new();
+        axes.insert("eat_ep".to_owned(), vec![Value::from(100), Value::from(200)]);
+        axes.insert("view_distance".to_owned(), vec![Value::from(2), Value::from(4), Value::from(6)]);
+        let mut c = campaign(BTreeMap::new());
+        c.sweep = Some(Sweep::Grid { axes, replicates: 2 });
+
+        let points = c.sweep_points();
+
+        assert_eq!(6, points.len());
+        assert_eq!(12, c.num_runs(&points));
+        let sp = c.sweep_point(7, &points).unwrap();
+        assert_eq!((3, 1), (sp.index, sp.replicate));
+        let p = c.make_params(&mut RNG::new(), Some(&sp)).unwrap();
+        assert_eq!((200, 2), (p.eat_ep, p.view_distance));
+    }
+
+    #[test]
+    fn latin_hypercube_sweep_samples_each_stratum_once() {
+        let mut axes = BTreeMap::new();
+        axes.insert("plant_prob_end".to_owned(), Range { min: 0.0, max: 0.5 });
+        axes.insert("ring_size".to_owned(), Range { min: 2.0, max: 6.0 });
+        let mut c = campaign(BTreeMap::new());
+        c.sweep = Some(Sweep::LatinHypercube { axes, samples: 5, replicates: 1 });
+
+        let points = c.sweep_points();
+
+        assert_eq!(5, points.len());
+        let mut strata: Vec<usize> = points.iter()
+            .map(|p| (p["plant_prob_end"].as_f64().unwrap() / 0.1) as usize).collect();
+        strata.sort();
+        assert_eq!(vec![0, 1, 2, 3, 4], strata);
+        let mut ring_sizes: Vec<u64> = points.iter().map(|p| p["ring_size"].as_u64().unwrap()).collect();
+        ring_sizes.sort();
+        assert_eq!(vec![2, 3, 4, 5, 6], ring_sizes);
+    }
+
+    #[test]
+    fn latin_hypercube_sweep_rejects_integer_axis_narrower_than_samples() {
+        let mut axes = BTreeMap::new();
+        axes.insert("ring_size".to_owned(), Range { min: 2.0, max: 4.5 });
+        let mut c = campaign(BTreeMap::new());
+        c.sweep = Some(Sweep::LatinHypercube { axes, samples: 4, replicates: 1 });
+
+        match c.check() {
+            Err(Error::InvalidParams(problems)) => assert_eq!(vec!["range for ring_size has fewer whole numbers than the 4 samples"], problems),
+            _ => panic!("expected the narrow axis to be rejected"),
+        }
+    }
+
+    #[test]
+    fn seeded_campaign_makes_identical_worlds() {
+        let mut randomize = BTreeMap::new();
+        randomize.insert("ring_size".to_owned(), (3..=9).map(Value::from).collect());
+        let mut c = campaign(randomize);
+        c.seed = Some([1, 2, 3, 4]);
+
+        let w0 = c.make_world(17, &[]).unwrap();
+        let w1 = c.make_world(17, &[]).unwrap();
+        let w2 = c.make_world(18, &[]).unwrap();
+
+        assert_eq!(w0.id, w1.id);
+        assert_eq!(w0.random.seed(), w1.random.seed());
+        assert_eq!(w0.params.ring_size, w1.params.ring_size);
+        assert_ne!(w0.random.seed(), w2.random.seed());
+    }
+
+    #[test]
+    fn index_entry_lists_key_params_and_result_file() {
+        let mut randomize = BTreeMap::new();
+        randomize.insert("ring_size".to_owned(), vec![Value::from(4)]);
+        let mut c = campaign(randomize);
+        c.seed = Some([1, 2, 3, 4]);
+        c.output.naming = crate::loader::Naming::SimNumber;
+
+        let w = c.make_world(7, &[]).unwrap();
+        let entry = c.index_entry(&w);
+
+        assert_eq!(vec!["ring_size"], entry.params.keys().collect::<Vec<_>>());
+        assert_eq!(Value::from(4), entry.params["ring_size"]);
+        assert!(entry.file.starts_with("7-"));
+        assert_eq!(w.random.seed(), &entry.seed);
+    }
+}
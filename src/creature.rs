@@ -1,13 +1,25 @@
 use std::cmp;
-use crate::program::*;
+use serde_derive::*;
+use crate::program::{self, *};
 use crate::params::Params;
 use crate::random::RNG;
 use crate::terrain::Terrain;
 use crate::genetics;
+use crate::genealogy::Record;
 use crate::log::Log;
 
 
+#[derive(Serialize, Deserialize)]
 pub struct Creature {
+    // ids are unique within a world; creatures placed at the start have no parents and are
+    // generation 0, offspring are one generation after the later of their parents
+    #[serde(default)]
+    pub id: u64,
+    #[serde(default)]
+    pub parents: Option<(u64, u64)>,
+    #[serde(default)]
+    pub generation: u32,
+
     pub program: Vec<Instr>,
     pub rsize: usize,
 
@@ -25,6 +37,9 @@ pub struct Creature {
 impl Creature {
     pub fn new(program: Vec<Instr>, params: &Params) -> Creature {
         Creature {
+            id: 0,
+            parents: None,
+            generation: 0,
             program,
             rsize: params.ring_size,
             bcycle: 0,
@@ -55,7 +70,7 @@ impl Creature {
     }
 
     pub fn current_instr(&self) -> Instr {
-        self.program[self.pc].clone()
+        self.program[self.pc]
     }
 
     pub fn add_to_bearing(&mut self, delta: u16) {
@@ -72,15 +87,12 @@ impl Creature {
         self.lastprocd - self.bcycle
     }
 
+    pub fn num_rings(&self) -> usize {
+        self.program.len() / self.rsize
+    }
+
     pub fn pp_program(&self) -> String {
-        let mut out = String::new();
-        for i in 0..self.program.len() {
-            out.push_str(&format!("{:?} ", self.program[i]));
-            if (i + 1) % self.rsize == 0 {
-                out.push_str("; ");
-            }
-        }
-        out
+        program::pp(&self.program, self.rsize)
     }
 
     // core processing loop
@@ -199,9 +211,14 @@ impl Creature {
 
     fn try_mate(&mut self, partner_pos: (u32, u32), offspring_pos: (u32, u32), ctx: &mut PContext) {
         if let Some(other) = ctx.terrain.creature_at(partner_pos) {
+            ctx.stats.count_mating_attempt();
             if self.can_mate(other, ctx) {
-                let offspring = self.mate(other, ctx.params, ctx.random, ctx.world_cycle);
+                let mut offspring = self.mate(other, ctx.params, ctx.random, ctx.stats, ctx.world_cycle);
+                offspring.id = *ctx.next_id;
+                *ctx.next_id += 1;
+                ctx.stats.record(|| Record::birth(&offspring));
                 ctx.terrain.set_creature_at(Some(offspring), offspring_pos);
+                ctx.stats.count_birth();
             }
         }
     }
@@ -211,13 +228,16 @@ impl Creature {
             other.age() > (ctx.params.creature_start_ep + ctx.params.eat_ep) as u64
     }
 
-    fn mate(&mut self, other: &Creature, params: &Params, random: &mut RNG, world_cycle: u64) -> Creature {
-        let program = genetics::cut_n_splice_crossover(&self.program, &other.program, random);
+    fn mate(&mut self, other: &Creature, params: &Params, random: &mut RNG, stats: &mut Log, world_cycle: u64) -> Creature {
+        let mut program = genetics::crossover(params.crossover, &self.program, &other.program, params.ring_size, random);
+        stats.count_mutations(genetics::mutate(&mut program, params, random));
         let mut offspring = Creature::new(program, params);
+        offspring.parents = Some((self.id, other.id));
+        offspring.generation = cmp::max(self.generation, other.generation) + 1;
         offspring.bcycle = world_cycle;
         offspring.bearing = random.choose(&[0, 90, 180, 270]);
         self.ep -= offspring.ep;
-        return offspring;
+        offspring
     }
 }
 
@@ -229,18 +249,20 @@ pub struct PContext<'a> {
     stats: &'a mut Log,
     random: &'a mut RNG,
     terrain: &'a mut Terrain,
+    next_id: &'a mut u64,
     world_cycle: u64,
     pos: (u32, u32),
 }
 
 impl<'a> PContext<'a> {
     pub fn new(params: &'a Params, stats: &'a mut Log, random: &'a mut RNG, terrain: &'a mut Terrain,
-               world_cycle: u64, pos: (u32, u32)) -> PContext<'a> {
+               next_id: &'a mut u64, world_cycle: u64, pos: (u32, u32)) -> PContext<'a> {
         PContext {
             params,
             stats,
             random,
             terrain,
+            next_id,
             world_cycle,
             pos,
         }
@@ -292,4 +314,30 @@ mod tests {
         c.pc_incr_ring();
         assert_eq!(0, c.pc)
     }
+
+    #[test]
+    fn programs_can_have_more_rings_than_start_programs() {
+        let c = Creature::new(vec![NOP, EAT, NOP, MOV, NOP, TUR, TUL, TUL, EAT, EAT, MOV, MOV], &Params::for_testing());
+        let mut c = Creature { pc: 10, ..c };
+        assert_eq!(4, c.num_rings());
+        assert_eq!("NOP EAT NOP ; MOV NOP TUR ; TUL TUL EAT ; EAT MOV MOV ; ", c.pp_program());
+        c.pc_incr_ring();
+        assert_eq!(0, c.pc)
+    }
+
+    #[test]
+    fn offspring_records_parents_and_generation() {
+        let params = Params::for_testing();
+        let mut c0 = Creature::new(vec![NOP, EAT, NOP, MOV, NOP, TUR], &params);
+        let mut c1 = Creature::new(vec![EAT, NOP, MOV, NOP, TUR, NOP], &params);
+        c0.id = 3;
+        c0.ep = 3 * params.creature_start_ep;
+        c1.id = 7;
+        c1.generation = 4;
+
+        let offspring = c0.mate(&c1, &params, &mut RNG::new(), &mut Log::new(), 100);
+
+        assert_eq!((Some((3, 7)), 5), (offspring.parents, offspring.generation));
+        assert_eq!(2 * params.creature_start_ep, c0.ep);
+    }
 }
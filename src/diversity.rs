@@ -0,0 +1,134 @@
+use std::cmp;
+use std::collections::BTreeMap;
+use serde_derive::*;
+use crate::program::Instr;
+use crate::random::RNG;
+
+
+// Measures of the genetic diversity of a population. The genotype of a creature is its
+// program. The Shannon entropy and the Simpson index describe the distribution of genotypes;
+// the Simpson index is the probability that two creatures picked at random have different
+// genotypes. The edit distance is the number of instructions that must be inserted, deleted or
+// replaced to turn one program into another. Its mean is estimated from a sample of pairs.
+
+pub const SAMPLED_PAIRS: usize = 200;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Diversity {
+    pub num_genotypes: u32,
+    pub shannon: f64,
+    pub simpson: f64,
+    pub mean_edit_distance: Option<f64>,
+    // for each position in the programs how many programs have which instruction there
+    pub instr_freqs: Vec<BTreeMap<Instr, u32>>,
+}
+
+
+impl Diversity {
+    pub fn of(programs: &[&[Instr]], rng: &mut RNG) -> Diversity {
+        // the genotypes are ordered to sum up the floats in the same order every time
+        let mut genotypes: BTreeMap<&[Instr], u32> = BTreeMap::new();
+        for p in programs {
+            *genotypes.entry(p).or_insert(0) += 1;
+        }
+        let n = programs.len() as f64;
+        let mut shannon = 0.0;
+        let mut sum_p2 = 0.0;
+        for count in genotypes.values() {
+            let p = *count as f64 / n;
+            shannon -= p * p.ln();
+            sum_p2 += p * p;
+        }
+        Diversity {
+            num_genotypes: genotypes.len() as u32,
+            shannon,
+            simpson: if programs.is_empty() { 0.0 } else { 1.0 - sum_p2 },
+            mean_edit_distance: mean_edit_distance(programs, rng),
+            instr_freqs: instr_freqs(programs),
+        }
+    }
+}
+
+
+fn mean_edit_distance(programs: &[&[Instr]], rng: &mut RNG) -> Option<f64> {
+    let n = programs.len();
+    if n < 2 {
+        return None;
+    }
+    let num_pairs = cmp::min(SAMPLED_PAIRS, n * (n - 1) / 2);
+    let mut total = 0;
+    for _ in 0..num_pairs {
+        let i = rng.next_usize(n);
+        let j = (i + 1 + rng.next_usize(n - 1)) % n;
+        total += edit_distance(programs[i], programs[j]);
+    }
+    Some(total as f64 / num_pairs as f64)
+}
+
+pub fn edit_distance(a: &[Instr], b: &[Instr]) -> usize {
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+    for (i, x) in a.iter().enumerate() {
+        curr[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let replace = prev[j] + if x == y { 0 } else { 1 };
+            curr[j + 1] = cmp::min(replace, cmp::min(prev[j + 1], curr[j]) + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+fn instr_freqs(programs: &[&[Instr]]) -> Vec<BTreeMap<Instr, u32>> {
+    let len = programs.iter().map(|p| p.len()).max().unwrap_or(0);
+    let mut freqs = vec![BTreeMap::new(); len];
+    for p in programs {
+        for (pos, instr) in p.iter().enumerate() {
+            *freqs[pos].entry(*instr).or_insert(0) += 1;
+        }
+    }
+    freqs
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program::Instr::*;
+    use crate::utils::round;
+
+    #[test]
+    fn edit_distance_counts_insertions_deletions_and_replacements() {
+        assert_eq!(0, edit_distance(&[EAT, MOV], &[EAT, MOV]));
+        assert_eq!(1, edit_distance(&[EAT, MOV], &[EAT, TUR]));
+        assert_eq!(1, edit_distance(&[EAT, MOV], &[EAT, NOP, MOV]));
+        assert_eq!(2, edit_distance(&[EAT, MOV, TUR], &[MOV]));
+        assert_eq!(3, edit_distance(&[], &[EAT, MOV, TUR]));
+    }
+
+    #[test]
+    fn uniform_population_has_no_diversity() {
+        let p = [EAT, MOV];
+        let programs: Vec<&[Instr]> = vec![&p, &p, &p];
+
+        let d = Diversity::of(&programs, &mut RNG::new());
+
+        assert_eq!(1, d.num_genotypes);
+        assert_eq!((0.0, 0.0), (d.shannon, d.simpson));
+        assert_eq!(Some(0.0), d.mean_edit_distance);
+        assert_eq!(Some(&3), d.instr_freqs[1].get(&MOV));
+    }
+
+    #[test]
+    fn two_equally_common_genotypes() {
+        let (p0, p1) = ([EAT, MOV], [EAT, TUR]);
+        let programs: Vec<&[Instr]> = vec![&p0, &p1, &p0, &p1];
+
+        let d = Diversity::of(&programs, &mut RNG::new());
+
+        assert_eq!(2, d.num_genotypes);
+        assert_eq!(round(2f64.ln(), 6), round(d.shannon, 6));
+        assert_eq!(0.5, d.simpson);
+        assert_eq!(Some(&2), d.instr_freqs[1].get(&TUR));
+    }
+}
@@ -0,0 +1,105 @@
+use std::fmt;
+use std::io;
+use serde_json::error::Category;
+
+
+#[derive(Debug)]
+pub enum Error {
+    Io { path: String, source: io::Error },
+    Json { path: String, line: usize, column: usize, message: String },
+    MissingField { path: String, field: String },
+    InvalidParams(Vec<String>),
+    InvalidProgram { line: usize, message: String },
+    InvalidPath(String),
+    UnsupportedVersion { path: String, version: u64 },
+    Campaign(String),
+    Divergence { cycle: u64, detail: String },
+    Interrupted,
+}
+
+
+impl Error {
+    pub fn io(path: &str, source: io::Error) -> Error {
+        Error::Io { path: path.to_owned(), source }
+    }
+
+    pub fn json(path: &str, err: serde_json::Error) -> Error {
+        let message = err.to_string();
+        // serde reports missing fields as data errors with a message like "missing field `seed`"
+        if err.classify() == Category::Data && message.starts_with("missing field `") {
+            if let Some(field) = message.split('`').nth(1) {
+                return Error::MissingField { path: path.to_owned(), field: field.to_owned() };
+            }
+        }
+        // the message ends with the position, which we report separately
+        let message = match message.rfind(" at line ") {
+            Some(i) => message[..i].to_owned(),
+            None => message,
+        };
+        Error::Json { path: path.to_owned(), line: err.line(), column: err.column(), message }
+    }
+}
+
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { path, source } =>
+                write!(f, "{}: {}", path, source),
+            // errors found after parsing, e.g. by migrations, have no position
+            Error::Json { path, line: 0, message, .. } =>
+                write!(f, "{}: {}", path, message),
+            Error::Json { path, line, column, message } =>
+                write!(f, "{}:{}:{}: {}", path, line, column, message),
+            Error::MissingField { path, field } =>
+                write!(f, "{}: missing field `{}`", path, field),
+            Error::InvalidParams(problems) =>
+                write!(f, "invalid parameters: {}", problems.join("; ")),
+            Error::InvalidProgram { line, message } =>
+                write!(f, "line {}: {}", line, message),
+            Error::InvalidPath(path) =>
+                write!(f, "{}: not a valid file path", path),
+            Error::UnsupportedVersion { path, version } =>
+                write!(f, "{}: format version {} is newer than this version of crellinor supports", path, version),
+            Error::Campaign(message) =>
+                write!(f, "{}", message),
+            Error::Divergence { cycle, detail } =>
+                write!(f, "replay diverged at cycle {}: {}", cycle, detail),
+            Error::Interrupted =>
+                write!(f, "stopped before all worlds ended; run the campaign again to continue"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_position_of_syntax_error() {
+        let err = serde_json::from_str::<serde_json::Value>("{\n  \"a\": ]\n}").unwrap_err();
+        match Error::json("world.json", err) {
+            Error::Json { line, column, .. } => assert_eq!((2, 8), (line, column)),
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn reports_missing_field_by_name() {
+        #[derive(serde_derive::Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct S { seed: u32 }
+        let err = serde_json::from_str::<S>("{}").unwrap_err();
+        assert_eq!("world.json: missing field `seed`", Error::json("world.json", err).to_string());
+    }
+}
@@ -0,0 +1,312 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use serde_derive::*;
+use crate::creature::Creature;
+use crate::error::Error;
+use crate::loader;
+use crate::log::Death;
+use crate::program::Instr;
+use crate::sink;
+use crate::utils;
+
+
+// The genealogy of a world lists every creature that was born in it, including the creatures
+// placed at the start, and every death. It is appended to a file next to the result, one
+// record per line:
+//   B <cycle> <id> <parent> <parent> <program>   birth; the parents are - for start creatures
+//   D <cycle> <id> <S|O>                         death by starvation or old age
+// The program is a hash of the creature's program, which is enough to tell whether two
+// creatures run the same program. Creatures without a death record were alive at the end.
+//
+// The genealogy can be exported as a tree in Newick format or as a graph in GraphML format.
+// Both are pruned to the ancestors of the creatures that were alive at the end. Newick can
+// only describe trees, so the Newick export follows the first parent, i.e. the creature that
+// initiated the mating and gave the offspring its energy, whereas the GraphML export has an
+// edge from each parent.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Record {
+    Birth { cycle: u64, id: u64, parents: Option<(u64, u64)>, program: u64 },
+    Death { cycle: u64, id: u64, cause: Death },
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TreeFormat {
+    Newick,
+    Graphml,
+}
+
+
+impl Record {
+    pub fn birth(c: &Creature) -> Record {
+        Record::Birth { cycle: c.bcycle, id: c.id, parents: c.parents, program: program_hash(&c.program) }
+    }
+
+    pub fn death(c: &Creature, cause: Death) -> Record {
+        Record::Death { cycle: c.lastprocd, id: c.id, cause }
+    }
+
+    pub fn cycle(&self) -> u64 {
+        match self {
+            Record::Birth { cycle, .. } | Record::Death { cycle, .. } => *cycle,
+        }
+    }
+
+    pub fn to_line(&self) -> String {
+        match self {
+            Record::Birth { cycle, id, parents: Some((p0, p1)), program } =>
+                format!("B {} {} {} {} {:016x}\n", cycle, id, p0, p1, program),
+            Record::Birth { cycle, id, parents: None, program } =>
+                format!("B {} {} - - {:016x}\n", cycle, id, program),
+            Record::Death { cycle, id, cause } =>
+                format!("D {} {} {}\n", cycle, id, match cause { Death::Starvation => "S", Death::OldAge => "O" }),
+        }
+    }
+
+    pub fn parse(line: &str) -> Option<Record> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields[..] {
+            ["B", cycle, id, p0, p1, program] => Some(Record::Birth {
+                cycle: cycle.parse().ok()?,
+                id: id.parse().ok()?,
+                parents: match (p0.parse().ok(), p1.parse().ok()) {
+                    (Some(p0), Some(p1)) => Some((p0, p1)),
+                    _ => None,
+                },
+                program: u64::from_str_radix(program, 16).ok()?,
+            }),
+            ["D", cycle, id, cause] => Some(Record::Death {
+                cycle: cycle.parse().ok()?,
+                id: id.parse().ok()?,
+                cause: match cause { "S" => Death::Starvation, "O" => Death::OldAge, _ => return None },
+            }),
+            _ => None,
+        }
+    }
+}
+
+pub fn program_hash(program: &[Instr]) -> u64 {
+    let text: String = program.iter().map(|instr| format!("{:?} ", instr)).collect();
+    utils::fnv1a(text.as_bytes())
+}
+
+
+pub struct GenealogyFile {
+    file: File,
+    path: String,
+}
+
+impl GenealogyFile {
+    // Like the log sinks, a world resumed from a checkpoint removes the records that were
+    // written after the checkpoint was taken, and a new world starts the file from scratch.
+    pub fn open(path: &str, resumed_at: Option<u64>) -> Result<GenealogyFile, Error> {
+        let file = sink::reopen(path, resumed_at.map(|c| c + 1), None, |l| Record::parse(l).map(|r| r.cycle()))?;
+        Ok(GenealogyFile { file, path: path.to_owned() })
+    }
+
+    pub fn write(&mut self, records: &[Record]) -> Result<(), Error> {
+        let text: String = records.iter().map(Record::to_line).collect();
+        self.file.write_all(text.as_bytes()).map_err(|e| Error::io(&self.path, e))
+    }
+}
+
+
+// Writes the genealogy of the run in the log file as a tree next to the genealogy file.
+pub fn export(logfile: &str, format: TreeFormat) -> Result<(), Error> {
+    let result = loader::load_result(logfile)?;
+    let genealogy_file = result.genealogy_file
+        .ok_or_else(|| Error::MissingField { path: logfile.to_owned(), field: "genealogy_file".to_owned() })?;
+    let path = Path::new(logfile).with_file_name(&genealogy_file);
+    let path_str = path.to_str().ok_or_else(|| Error::InvalidPath(genealogy_file.clone()))?;
+    let text = fs::read_to_string(path_str).map_err(|e| Error::io(path_str, e))?;
+    let records: Vec<Record> = text.lines().filter_map(Record::parse).collect();
+
+    let (tree, extension) = match format {
+        TreeFormat::Newick => (newick(&records), "nwk"),
+        TreeFormat::Graphml => (graphml(&records), "graphml"),
+    };
+    let dir = path.parent().map_or(".".to_owned(), |p| p.to_string_lossy().into_owned());
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("genealogy");
+    let filename = format!("{}.{}", stem, extension);
+    loader::write_file(&dir, &filename, &tree)?;
+    println!("Wrote {}/{}", dir, filename);
+    Ok(())
+}
+
+
+struct Node {
+    birth: u64,
+    parents: Option<(u64, u64)>,
+    program: u64,
+    death: Option<(u64, Death)>,
+}
+
+fn nodes(records: &[Record]) -> BTreeMap<u64, Node> {
+    let mut nodes = BTreeMap::new();
+    for r in records {
+        match r {
+            Record::Birth { cycle, id, parents, program } => {
+                nodes.insert(*id, Node { birth: *cycle, parents: *parents, program: *program, death: None });
+            }
+            Record::Death { cycle, id, cause } => {
+                if let Some(n) = nodes.get_mut(id) {
+                    n.death = Some((*cycle, *cause));
+                }
+            }
+        }
+    }
+    nodes
+}
+
+// The survivors and all their ancestors, following either only the first or both parents.
+fn ancestors_of_survivors(nodes: &BTreeMap<u64, Node>, both_parents: bool) -> BTreeSet<u64> {
+    let mut kept = BTreeSet::new();
+    let mut todo: Vec<u64> = nodes.iter().filter(|(_, n)| n.death.is_none()).map(|(id, _)| *id).collect();
+    while let Some(id) = todo.pop() {
+        if !kept.insert(id) {
+            continue;
+        }
+        if let Some((p0, p1)) = nodes[&id].parents {
+            todo.extend(std::iter::once(p0).chain(if both_parents { Some(p1) } else { None })
+                .filter(|p| nodes.contains_key(p)));
+        }
+    }
+    kept
+}
+
+pub fn newick(records: &[Record]) -> String {
+    let nodes = nodes(records);
+    let kept = ancestors_of_survivors(&nodes, false);
+    let mut roots = Vec::new();
+    let mut children: BTreeMap<u64, Vec<u64>> = BTreeMap::new();
+    for id in &kept {
+        match nodes[id].parents {
+            Some((p0, _)) if kept.contains(&p0) => children.entry(p0).or_default().push(*id),
+            _ => roots.push(*id),
+        }
+    }
+    let label = |id: u64| {
+        let parent_birth = nodes[&id].parents.and_then(|(p0, _)| nodes.get(&p0)).map_or(0, |p| p.birth);
+        format!("{}:{}", id, nodes[&id].birth.saturating_sub(parent_birth))
+    };
+
+    // lineages can be very long, which is why the tree isn't written recursively
+    let mut out = String::new();
+    let mut steps = separated(&roots);
+    while let Some(step) = steps.pop() {
+        match step {
+            Step::Enter(id) => match children.get(&id) {
+                Some(c) => {
+                    out.push('(');
+                    steps.push(Step::Exit(id));
+                    steps.extend(separated(c));
+                }
+                None => out.push_str(&label(id)),
+            },
+            Step::Exit(id) => {
+                out.push(')');
+                out.push_str(&label(id));
+            }
+            Step::Separator => out.push(','),
+        }
+    }
+    if roots.len() > 1 { format!("({});\n", out) } else { format!("{};\n", out) }
+}
+
+enum Step {
+    Enter(u64),
+    Exit(u64),
+    Separator,
+}
+
+// steps are taken from the end, so they are pushed in reverse
+fn separated(ids: &[u64]) -> Vec<Step> {
+    let mut steps = Vec::new();
+    for (i, id) in ids.iter().rev().enumerate() {
+        if i > 0 {
+            steps.push(Step::Separator);
+        }
+        steps.push(Step::Enter(*id));
+    }
+    steps
+}
+
+pub fn graphml(records: &[Record]) -> String {
+    let nodes = nodes(records);
+    let kept = ancestors_of_survivors(&nodes, true);
+    let mut out = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+        "  <key id=\"birth\" for=\"node\" attr.name=\"birth\" attr.type=\"long\"/>\n",
+        "  <key id=\"death\" for=\"node\" attr.name=\"death\" attr.type=\"long\"/>\n",
+        "  <key id=\"cause\" for=\"node\" attr.name=\"cause\" attr.type=\"string\"/>\n",
+        "  <key id=\"program\" for=\"node\" attr.name=\"program\" attr.type=\"string\"/>\n",
+        "  <graph id=\"genealogy\" edgedefault=\"directed\">\n"));
+    for id in &kept {
+        let n = &nodes[id];
+        out.push_str(&format!("    <node id=\"c{}\">\n      <data key=\"birth\">{}</data>\n", id, n.birth));
+        if let Some((cycle, cause)) = n.death {
+            let cause = match cause { Death::Starvation => "starvation", Death::OldAge => "old_age" };
+            out.push_str(&format!("      <data key=\"death\">{}</data>\n      <data key=\"cause\">{}</data>\n", cycle, cause));
+        }
+        out.push_str(&format!("      <data key=\"program\">{:016x}</data>\n    </node>\n", n.program));
+    }
+    for id in &kept {
+        if let Some((p0, p1)) = nodes[id].parents {
+            for p in [p0, p1].iter().filter(|p| kept.contains(p)) {
+                out.push_str(&format!("    <edge source=\"c{}\" target=\"c{}\"/>\n", p, id));
+            }
+        }
+    }
+    out.push_str("  </graph>\n</graphml>\n");
+    out
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn birth(cycle: u64, id: u64, parents: Option<(u64, u64)>) -> Record {
+        Record::Birth { cycle, id, parents, program: id }
+    }
+
+    fn death(cycle: u64, id: u64) -> Record {
+        Record::Death { cycle, id, cause: Death::Starvation }
+    }
+
+    // 1 and 2 are the start creatures; 3 and 4 are their offspring, and 5 is an offspring of
+    // 4 and 2. Only 4 and 5 survive.
+    fn records() -> Vec<Record> {
+        vec![birth(0, 1, None), birth(0, 2, None), birth(100, 3, Some((1, 2))), birth(150, 4, Some((1, 2))),
+             death(180, 3), birth(200, 5, Some((4, 2))), death(300, 1), death(300, 2)]
+    }
+
+    #[test]
+    fn records_are_read_back_from_their_lines() {
+        let records = vec![birth(100, 3, Some((1, 2))), birth(0, 1, None), Record::Death { cycle: 7, id: 3, cause: Death::OldAge }];
+        for r in records {
+            assert_eq!(Some(r.clone()), Record::parse(&r.to_line()));
+        }
+    }
+
+    #[test]
+    fn newick_follows_first_parents_of_survivors() {
+        assert_eq!("((5:50)4:150)1:0;\n", newick(&records()));
+    }
+
+    #[test]
+    fn graphml_has_an_edge_from_each_parent() {
+        let g = graphml(&records());
+
+        assert!(!g.contains("<node id=\"c3\">"));
+        assert!(g.contains("<edge source=\"c2\" target=\"c5\"/>"));
+        assert!(g.contains("<edge source=\"c4\" target=\"c5\"/>"));
+        assert_eq!(4, g.matches("<edge ").count());
+        assert!(g.contains("<data key=\"cause\">starvation</data>"));
+    }
+}
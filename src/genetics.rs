@@ -1,31 +1,150 @@
+use serde_derive::*;
+use crate::params::Params;
+use crate::program;
 use crate::program::Instr;
 use crate::random::RNG;
 
 
-pub fn single_point_crossover<'a>(p0: &Vec<Instr>, p1: &Vec<Instr>, rng: &'a mut RNG) -> Vec<Instr> {
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Crossover {
+    SinglePoint,
+    TwoPoint,
+    Uniform,
+    RingAligned,
+    #[default]
+    CutAndSplice,
+}
+
+// When the number of rings can change the parents' programs can have different lengths. All
+// operators return a program with the length of one of the parents.
+pub fn crossover(kind: Crossover, p0: &[Instr], p1: &[Instr], ring_size: usize, rng: &mut RNG) -> Vec<Instr> {
+    match kind {
+        Crossover::SinglePoint => single_point_crossover(p0, p1, rng),
+        Crossover::TwoPoint => two_point_crossover(p0, p1, rng),
+        Crossover::Uniform => uniform_crossover(p0, p1, rng),
+        Crossover::RingAligned => ring_aligned_crossover(p0, p1, ring_size, rng),
+        Crossover::CutAndSplice => cut_n_splice_crossover(p0, p1, rng),
+    }
+}
+
+
+pub fn single_point_crossover(p0: &[Instr], p1: &[Instr], rng: &mut RNG) -> Vec<Instr> {
     let (left, right) = [(p0, p1), (p1, p0)][rng.next_usize(2)];
-    let xpt = rng.next_usize(p0.len());
+    let xpt = rng.next_usize(p0.len().min(p1.len()));
     let mut result = left[..xpt].to_vec();
     result.extend_from_slice(&right[xpt..]);
     result
 }
 
 
-pub fn cut_n_splice_crossover<'a>(p0: &Vec<Instr>, p1: &Vec<Instr>, rng: &'a mut RNG) -> Vec<Instr> {
-    let l = rng.next_usize(p1.len() - 1);
+// Replaces the instructions between two points in p0 with those in p1.
+pub fn two_point_crossover(p0: &[Instr], p1: &[Instr], rng: &mut RNG) -> Vec<Instr> {
+    let len = p0.len().min(p1.len());
+    let a = rng.next_usize(len + 1);
+    let b = rng.next_usize(len + 1);
+    let (from, to) = if a <= b { (a, b) } else { (b, a) };
+    let mut result = p0.to_vec();
+    result[from..to].copy_from_slice(&p1[from..to]);
+    result
+}
+
+// Takes each instruction from either parent. Instructions beyond the end of p1 are taken from p0.
+pub fn uniform_crossover(p0: &[Instr], p1: &[Instr], rng: &mut RNG) -> Vec<Instr> {
+    let mut result: Vec<Instr> = p0.iter().zip(p1).map(|(i0, i1)| if rng.next_usize(2) == 0 { *i0 } else { *i1 }).collect();
+    result.extend_from_slice(&p0[result.len()..]);
+    result
+}
+
+// Takes each ring from either parent. Rings beyond the last ring of p1 are taken from p0.
+pub fn ring_aligned_crossover(p0: &[Instr], p1: &[Instr], ring_size: usize, rng: &mut RNG) -> Vec<Instr> {
+    let mut result: Vec<Instr> = p0.chunks(ring_size).zip(p1.chunks(ring_size))
+        .flat_map(|(r0, r1)| if rng.next_usize(2) == 0 { r0 } else { r1 })
+        .cloned()
+        .collect();
+    result.extend_from_slice(&p0[result.len()..]);
+    result
+}
+
+
+pub fn cut_n_splice_crossover(p0: &[Instr], p1: &[Instr], rng: &mut RNG) -> Vec<Instr> {
+    let l = rng.next_usize(p0.len().min(p1.len()) - 1);
     let s = rng.next_usize(p1.len() - l);
     let d = rng.next_usize(p0.len() - l);
-    let mut pc = p0.clone();
+    let mut pc = p0.to_vec();
     pc.splice(d..d + l, p1[s..s + l].iter().cloned());
     pc
 }
 
-pub fn mutation<'a>(p: &mut Vec<Instr>, instr_list: Vec<&Instr>, rng:&'a mut RNG) {
+// Applies each kind of mutation with the probability given in the params, and returns how
+// many mutations were applied.
+pub fn mutate(p: &mut Vec<Instr>, params: &Params, rng: &mut RNG) -> u32 {
+    let mut count = 0;
+    if happens(params.point_mutation_rate, rng) {
+        mutation(p, params.instr_list(), rng);
+        count += 1;
+    }
+    let (min_rings, max_rings) = params.ring_count_range();
+    if happens(params.ring_duplication_rate, rng) {
+        ring_duplication(p, params.ring_size, max_rings, rng);
+        count += 1;
+    }
+    if happens(params.ring_deletion_rate, rng) {
+        ring_deletion(p, params.ring_size, min_rings, params.instr_list(), rng);
+        count += 1;
+    }
+    if happens(params.swap_mutation_rate, rng) {
+        swap_mutation(p, rng);
+        count += 1;
+    }
+    count
+}
+
+// a rate of 0 doesn't use the random number generator, so that worlds without mutations
+// continue to run as they did before mutations were added
+fn happens(rate: f64, rng: &mut RNG) -> bool {
+    rate > 0.0 && rng.next_f64() < rate
+}
+
+pub fn mutation(p: &mut [Instr], instr_list: Vec<&Instr>, rng: &mut RNG) {
     let mpt = rng.next_usize(p.len());
-    let instr = instr_list[rng.next_usize(instr_list.len())].clone();
+    let instr = *instr_list[rng.next_usize(instr_list.len())];
     p[mpt] = instr;
 }
 
+// Inserts a copy of a ring before any ring or at the end. A program that has the maximum
+// number of rings already keeps its length; the copy replaces a ring, which can be the
+// copied ring itself.
+pub fn ring_duplication(p: &mut Vec<Instr>, ring_size: usize, max_rings: usize, rng: &mut RNG) {
+    let num_rings = p.len() / ring_size;
+    let src = rng.next_usize(num_rings) * ring_size;
+    if num_rings < max_rings {
+        let dest = rng.next_usize(num_rings + 1) * ring_size;
+        let ring = p[src..src + ring_size].to_vec();
+        p.splice(dest..dest, ring);
+    } else {
+        let dest = rng.next_usize(num_rings) * ring_size;
+        p.copy_within(src..src + ring_size, dest);
+    }
+}
+
+// Removes a ring, and the rings after it move up. A program that has the minimum number of
+// rings already keeps its length; a ring of random instructions is added at the end.
+pub fn ring_deletion(p: &mut Vec<Instr>, ring_size: usize, min_rings: usize, instr_list: Vec<&Instr>, rng: &mut RNG) {
+    let num_rings = p.len() / ring_size;
+    let start = rng.next_usize(num_rings) * ring_size;
+    p.drain(start..start + ring_size);
+    if num_rings <= min_rings {
+        p.append(&mut program::rand_program(instr_list, ring_size, rng));
+    }
+}
+
+pub fn swap_mutation(p: &mut [Instr], rng: &mut RNG) {
+    let i = rng.next_usize(p.len());
+    let j = rng.next_usize(p.len());
+    p.swap(i, j);
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -57,6 +176,42 @@ mod tests {
         assert_eq!([TUR, TUL], pc.as_slice());
     }
 
+    #[test]
+    fn two_point_crossover_takes_middle_from_p1() {
+        let mut rng = RNG::new();
+        rng.set_next_values(&[4, 1]);
+        let p0 = vec![TUL, TUL, TUL, TUL, TUL];
+        let p1 = vec![MOV, NOP, NOP, EAT, MOV];
+
+        let pc = two_point_crossover(&p0, &p1, &mut rng);
+
+        assert_eq!([TUL, NOP, NOP, EAT, TUL], pc.as_slice());
+    }
+
+    #[test]
+    fn uniform_crossover_takes_each_instruction_from_either_parent() {
+        let mut rng = RNG::new();
+        rng.set_next_values(&[0, 1, 1, 0]);
+        let p0 = vec![TUL, TUL, TUL, TUL];
+        let p1 = vec![MOV, NOP, EAT, MOV];
+
+        let pc = uniform_crossover(&p0, &p1, &mut rng);
+
+        assert_eq!([TUL, NOP, EAT, TUL], pc.as_slice());
+    }
+
+    #[test]
+    fn ring_aligned_crossover_takes_whole_rings() {
+        let mut rng = RNG::new();
+        rng.set_next_values(&[1, 0, 1]);
+        let p0 = vec![TUL, TUL, TUL, TUL, TUL, TUL];
+        let p1 = vec![MOV, NOP, EAT, MOV, NOP, EAT];
+
+        let pc = ring_aligned_crossover(&p0, &p1, 2, &mut rng);
+
+        assert_eq!([MOV, NOP, TUL, TUL, NOP, EAT], pc.as_slice());
+    }
+
     #[test]
     fn cut_n_splice_crossover_with_move() {
         let mut rng = RNG::new();
@@ -81,4 +236,89 @@ mod tests {
         assert_eq!([MOV, EAT, TUL, NOP], p.as_slice());
 
     }
+
+    #[test]
+    fn ring_duplication_copies_ring() {
+        let mut rng = RNG::new();
+        rng.set_next_values(&[2 /* src ring */, 0 /* dest ring */]);
+        let mut p = vec![MOV, EAT, NOP, NOP, TUR, TUL];
+
+        ring_duplication(&mut p, 2, 3, &mut rng);
+
+        assert_eq!([TUR, TUL, NOP, NOP, TUR, TUL], p.as_slice());
+    }
+
+    #[test]
+    fn ring_duplication_inserts_ring_below_maximum() {
+        let mut rng = RNG::new();
+        rng.set_next_values(&[2 /* src ring */, 3 /* dest ring */]);
+        let mut p = vec![MOV, EAT, NOP, NOP, TUR, TUL];
+
+        ring_duplication(&mut p, 2, 4, &mut rng);
+
+        assert_eq!([MOV, EAT, NOP, NOP, TUR, TUL, TUR, TUL], p.as_slice());
+    }
+
+    #[test]
+    fn ring_deletion_moves_rings_up_and_adds_random_ring() {
+        let mut rng = RNG::new();
+        let params = Params::for_testing();
+        rng.set_next_values(&[0 /* ring */, 0 /* instr */, 1 /* instr */]);
+        let mut p = vec![MOV, EAT, NOP, NOP, TUR, TUL];
+
+        ring_deletion(&mut p, 2, 3, params.instr_list(), &mut rng);
+
+        assert_eq!([NOP, NOP, TUR, TUL, NOP, TUR], p.as_slice());
+    }
+
+    #[test]
+    fn ring_deletion_removes_ring_above_minimum() {
+        let mut rng = RNG::new();
+        let params = Params::for_testing();
+        rng.set_next_values(&[1 /* ring */]);
+        let mut p = vec![MOV, EAT, NOP, NOP, TUR, TUL];
+
+        ring_deletion(&mut p, 2, 2, params.instr_list(), &mut rng);
+
+        assert_eq!([MOV, EAT, TUR, TUL], p.as_slice());
+    }
+
+    #[test]
+    fn crossovers_accept_parents_of_different_lengths() {
+        let p0 = vec![TUL, TUL, TUL, TUL];
+        let p1 = vec![MOV, NOP, EAT, MOV, NOP, EAT];
+        for kind in [Crossover::SinglePoint, Crossover::TwoPoint, Crossover::Uniform, Crossover::RingAligned,
+                        Crossover::CutAndSplice] {
+            for _ in 0..20 {
+                let pc = crossover(kind, &p0, &p1, 2, &mut RNG::new());
+                assert!(pc.len() == p0.len() || pc.len() == p1.len());
+                let pc = crossover(kind, &p1, &p0, 2, &mut RNG::new());
+                assert!(pc.len() == p0.len() || pc.len() == p1.len());
+            }
+        }
+    }
+
+    #[test]
+    fn swap_mutation_swaps_instructions() {
+        let mut rng = RNG::new();
+        rng.set_next_values(&[0, 3]);
+        let mut p = vec![MOV, EAT, NOP, TUR];
+
+        swap_mutation(&mut p, &mut rng);
+
+        assert_eq!([TUR, EAT, NOP, MOV], p.as_slice());
+    }
+
+    #[test]
+    fn mutate_applies_mutations_with_their_rates() {
+        let mut params = Params::for_testing();
+        let mut p = vec![MOV, EAT, NOP, TUR, TUL, BFA];
+
+        assert_eq!(0, mutate(&mut p.clone(), &params, &mut RNG::new()));
+
+        params.point_mutation_rate = 1.0;
+        params.swap_mutation_rate = 1.0;
+        assert_eq!(2, mutate(&mut p, &params, &mut RNG::new()));
+        assert_eq!(6, p.len());
+    }
 }
@@ -1,4 +1,17 @@
+#![cfg_attr(test, allow(clippy::bool_assert_comparison))]
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use serde_json::Value;
+
+pub mod analyze;
+pub mod campaign;
 pub mod creature;
+pub mod diversity;
+pub mod error;
+pub mod genealogy;
 pub mod genetics;
 pub mod program;
 pub mod multiverse;
@@ -7,21 +20,159 @@ pub mod params;
 pub mod plant;
 pub mod terrain;
 pub mod random;
+pub mod sink;
 pub mod log;
 pub mod loader;
 pub mod world;
 pub mod web;
 
-pub fn run(worldfile_opt: Option<String>, run_web: bool)
-{
-    if let Some(worldfile) = worldfile_opt {
-        let mut world = loader::load_world(&worldfile);
-        if run_web {
-            web::run(world, "resources/ui", "localhost:3000");
-        } else {
-            world.run();
+pub use crate::error::Error;
+use crate::genealogy::TreeFormat;
+use crate::loader::OutputOptions;
+use crate::random::RNG;
+use crate::world::World;
+
+
+pub enum Command {
+    Run { worldfile: String, seed: Option<[u32; 4]>, overrides: BTreeMap<String, Value>, checkpoint_at: Option<u64>,
+          output: OutputOptions },
+    Resume { checkpoint: String, checkpoint_at: Option<u64>, output: OutputOptions },
+    Campaign { campaignfile: Option<String>, output: OutputOptions, stop_flag: Arc<AtomicBool> },
+    Serve { path: String, from_checkpoint: bool, addr: String, ui_path: String },
+    Replay { logfile: String },
+    Check { worldfile: String },
+    Analyze { dir: String, per_run: bool },
+    Genealogy { logfile: String, format: TreeFormat },
+    Disasm { file: String, ring_size: usize },
+}
+
+
+pub fn run(command: Command) -> Result<(), Error> {
+    match command {
+        Command::Run { worldfile, seed, overrides, checkpoint_at, output } =>
+            run_world(&worldfile, seed, &overrides, checkpoint_at, &output),
+        Command::Resume { checkpoint, checkpoint_at, output } => {
+            let mut world = loader::load_checkpoint(&checkpoint)?;
+            world.checkpoint_at = checkpoint_at;
+            output.apply_to(&mut world.output);
+            world.continue_run()
+        }
+        Command::Campaign { campaignfile, output, stop_flag } => {
+            let mut campaign = match campaignfile {
+                Some(path) => loader::load_campaign(&path)?,
+                None => multiverse::default_campaign(),
+            };
+            output.apply_to(&mut campaign.output);
+            multiverse::run(campaign, stop_flag)
+        }
+        Command::Serve { path, from_checkpoint, addr, ui_path } => {
+            let world = if from_checkpoint {
+                loader::load_checkpoint(&path)?
+            } else {
+                let mut world = loader::load_world(&path)?;
+                world.add_initial_plants_and_creatures();
+                world
+            };
+            web::run(world, &ui_path, &addr);
+            Ok(())
+        }
+        Command::Replay { logfile } =>
+            replay(&logfile),
+        Command::Check { worldfile } =>
+            check(&worldfile),
+        Command::Analyze { dir, per_run } =>
+            analyze::run(&dir, per_run),
+        Command::Genealogy { logfile, format } =>
+            genealogy::export(&logfile, format),
+        Command::Disasm { file, ring_size } =>
+            disasm(&file, ring_size),
+    }
+}
+
+
+fn run_world(worldfile: &str, seed: Option<[u32; 4]>, overrides: &BTreeMap<String, Value>,
+             checkpoint_at: Option<u64>, output: &OutputOptions) -> Result<(), Error> {
+    let mut world = loader::load_world_with(worldfile, overrides)?;
+    if let Some(seed) = seed {
+        world.random = RNG::from_seed(&seed);
+    }
+    world.checkpoint_at = checkpoint_at;
+    output.apply_to(&mut world.output);
+    let (of_world, others) = loader::find_rolling_checkpoints_of(&world);
+    for path in &others {
+        println!("Not resuming {}, which belongs to a run with a different seed or different params.", path.display());
+    }
+    if let Some(path) = of_world.first() {
+        println!("Found checkpoint of an unfinished run.");
+        let output = world.output;
+        let mut world = loader::load_checkpoint(path.to_str().unwrap())?;
+        world.checkpoint_at = checkpoint_at;
+        world.output = output;
+        return world.continue_run();
+    }
+    world.run()
+}
+
+
+// Reruns a world from the params and seed in its log file, without writing a result, and
+// reports the first cycle at which the log of the rerun differs from the recorded log, or
+// whether the rerun ended differently. Log entries streamed to a CSV file are not read back,
+// so for those runs, as for runs without log entries, only the ends are compared.
+
+fn replay(logfile: &str) -> Result<(), Error> {
+    let recorded = loader::load_result(logfile)?;
+    let mut world: World = loader::load_world(logfile)?;
+    world.params.checkpoint_interval = 0;
+    world.add_initial_plants_and_creatures();
+    world.do_cycles_until_end()?;
+    println!("Recorded run ended after {} cycles ({}).", recorded.cycles, recorded.status);
+    println!("Replayed run ended after {} cycles ({}).", world.cycle, world.status());
+    let (recorded_entries, replayed_entries) = (&recorded.log.entries, &world.log.entries);
+    if !recorded_entries.is_empty() {
+        for (i, recorded_entry) in recorded_entries.iter().enumerate() {
+            let cycle = recorded_entry.cycle();
+            let differences = match replayed_entries.get(i) {
+                Some(e) if e.cycle() == cycle => recorded_entry.differences(e),
+                _ => vec!["the replayed run has no log entry for this cycle".to_owned()],
+            };
+            if !differences.is_empty() {
+                return Err(Error::Divergence { cycle, detail: differences.join("; ") });
+            }
+        }
+        if let Some(e) = replayed_entries.get(recorded_entries.len()) {
+            return Err(Error::Divergence { cycle: e.cycle(), detail: "the recorded run has no log entry for this cycle".to_owned() });
         }
+        println!("All {} log entries are the same.", recorded_entries.len());
+    } else if let Some(log_file) = recorded.log_file.as_ref().filter(|f| f.ends_with(".csv")) {
+        println!("The log entries in {} were not compared, only the ends of the runs.", log_file);
     } else {
-        multiverse::run();
+        println!("The recorded run has no log entries, only the ends of the runs were compared.");
+    }
+    if recorded.cycles != world.cycle || recorded.status != world.status() {
+        return Err(Error::Divergence { cycle: world.cycle, detail: "replay ended differently".to_owned() });
+    }
+    Ok(())
+}
+
+
+fn check(worldfile: &str) -> Result<(), Error> {
+    let validation = loader::load_params(worldfile)?.validate();
+    for warning in &validation.warnings {
+        println!("Warning: {}", warning);
+    }
+    if !validation.is_ok() {
+        return Err(Error::InvalidParams(validation.errors));
     }
+    println!("{}: parameters are valid", worldfile);
+    Ok(())
+}
+
+
+// Reads a program, e.g. one pasted from a log entry, and prints it with one ring per line.
+
+fn disasm(file: &str, ring_size: usize) -> Result<(), Error> {
+    let text = fs::read_to_string(file).map_err(|e| Error::io(file, e))?;
+    let program = program::parse(&text, ring_size)?;
+    print!("{}", program::disassemble(&program, ring_size));
+    Ok(())
 }
@@ -1,15 +1,24 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fs;
 use std::fs::File;
 use std::io::Write;
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use serde_json;
 use serde_derive::*;
-use serde_json::{json, to_string_pretty};
-use uuid::Uuid;
+use serde_json::{json, to_string_pretty, Value};
+use serde::de::DeserializeOwned;
+use crate::campaign::{Campaign, Index, SweepPoint};
+use crate::creature::Creature;
+use crate::error::Error;
+use crate::log::{Log, LogEntry};
+use crate::sink::LogSinkKind;
 use crate::params::Params;
+use crate::plant::Plant;
 use crate::world::World;
 use crate::random::RNG;
+use crate::utils;
 
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -19,53 +28,629 @@ struct Worldfile {
 }
 
 impl Worldfile {
-    pub fn from_str(s: &str) -> Result<Worldfile, &'static str> {
-        let wf: Worldfile = serde_json::from_str(s).expect("Failed to parse JSON");
-        Ok(wf)
+    pub fn from_file(filename: &str) -> Result<Worldfile, Error> {
+        parse_document(filename, &read_file(filename)?)
     }
+}
+
+// The result of a run, as written to the log file when the run ends. Because it has params and
+// a seed a log file can also be used as a world file. The log is borrowed when a result is
+// written from a world, so that it doesn't have to be copied.
+#[derive(Serialize, Deserialize)]
+pub struct RunResult<'a> {
+    #[serde(default)]
+    pub format_version: u32,
+    pub id: String,
+    pub params: Params,
+    pub seed: [u32; 4],
+    pub cycles: u64,
+    pub status: String,
+    #[serde(default)]
+    pub sim_num: Option<u32>,
+    #[serde(default)]
+    pub sweep_point: Option<SweepPoint>,
+    // when the log entries were streamed, the file they were written to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub genealogy_file: Option<String>,
+    #[serde(rename = "x-log")]
+    pub log: Cow<'a, Log>,
+}
 
-    pub fn from_file(filename: &str) -> Result<Worldfile, &'static str> {
-        let mut file = File::open(filename).expect(&format!("Failed to open file {}", filename));
-        let mut contents = String::new();
-        file.read_to_string(&mut contents).expect(&format!("Failed to read file {}", filename));
-        Worldfile::from_str(&contents)
+impl<'a> RunResult<'a> {
+    pub fn from_world(w: &'a World) -> RunResult<'a> {
+        RunResult {
+            format_version: FORMAT_VERSION,
+            id: w.id.clone(),
+            params: w.params.clone(),
+            seed: *w.random.seed(),
+            cycles: w.cycle,
+            status: w.status().to_owned(),
+            sim_num: w.sim_num,
+            sweep_point: w.sweep_point.clone(),
+            log_file: w.output.log_filename(w),
+            genealogy_file: w.output.genealogy_filename(w),
+            log: Cow::Borrowed(&w.log),
+        }
     }
 }
 
-pub fn load_world(path: &str) -> World {
-    let name = Path::new(path).file_stem().unwrap().to_str().unwrap();
+// Loads a result, including the log entries that were streamed to an ndjson file. Entries
+// written to a CSV file are not read back.
+pub fn load_result(path: &str) -> Result<RunResult<'static>, Error> {
+    let mut result: RunResult = parse_document(path, &read_file(path)?)?;
+    if let Some(log_file) = result.log_file.as_ref().filter(|f| f.ends_with(".ndjson")) {
+        let stream_path = Path::new(path).with_file_name(log_file);
+        let stream_path = stream_path.to_str().ok_or_else(|| Error::InvalidPath(log_file.clone()))?;
+        let mut entries = parse_log_stream(stream_path, &read_file(stream_path)?)?;
+        // an interrupted run also has the entry that was being written to when it stopped
+        let log = result.log.to_mut();
+        entries.append(&mut log.entries);
+        log.entries = entries;
+    }
+    Ok(result)
+}
+
+fn parse_log_stream(path: &str, s: &str) -> Result<Vec<LogEntry>, Error> {
+    s.lines().filter(|l| !l.is_empty()).map(|l| parse_json(path, l)).collect()
+}
+
+// Finds the result files below the directory. These are the files named log-<id>.json and the
+// files listed in a campaign index, which may be named differently.
+pub fn find_results(dir: &str) -> Result<Vec<PathBuf>, Error> {
+    let mut found = Vec::new();
+    let entries = fs::read_dir(dir).map_err(|e| Error::io(dir, e))?;
+    let mut paths: Vec<PathBuf> = entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+    paths.sort();
+    let index_path = format!("{}/index.json", dir);
+    if Path::new(&index_path).exists() {
+        let index: Index = parse_json(&index_path, &read_file(&index_path)?)?;
+        found.extend(index.runs.values().map(|r| Path::new(dir).join(&r.file)).filter(|p| p.exists()));
+    }
+    for p in paths {
+        let filename = p.file_name().and_then(|f| f.to_str()).unwrap_or("");
+        if p.is_dir() {
+            found.extend(find_results(p.to_str().ok_or_else(|| Error::InvalidPath(format!("{:?}", p)))?)?);
+        } else if filename.starts_with("log-") && filename.ends_with(".json") && !found.contains(&p) {
+            found.push(p);
+        }
+    }
+    Ok(found)
+}
+
+pub fn load_world(path: &str) -> Result<World, Error> {
+    load_world_with(path, &BTreeMap::new())
+}
+
+// Loads a world, replacing the named params with the given values.
+pub fn load_world_with(path: &str, overrides: &BTreeMap<String, Value>) -> Result<World, Error> {
+    let name = Path::new(path).file_stem().and_then(|s| s.to_str())
+        .ok_or_else(|| Error::InvalidPath(path.to_owned()))?;
     println!("Loading world from {}", path);
-    let wf = Worldfile::from_file(path).expect("Failed to load worldfile");
-    let mut w = World::new(name, wf.params);
+    let wf = Worldfile::from_file(path)?;
+    let params = wf.params.with_values(overrides)?;
+    for warning in params.validate().warnings {
+        println!("Warning: {}", warning);
+    }
+    let mut w = World::new(name, params)?;
     w.random = RNG::from_seed(&wf.seed);
-    w
+    Ok(w)
+}
+
+pub fn load_campaign(path: &str) -> Result<Campaign, Error> {
+    println!("Loading campaign from {}", path);
+    parse_document(path, &read_file(path)?)
 }
 
+pub fn write_campaign(c: &Campaign) -> Result<(), Error> {
+    let mut doc = serde_json::to_value(c).unwrap();
+    doc["format_version"] = Value::from(FORMAT_VERSION);
+    write_file(&c.output.dir(&c.name), "campaign.json", &to_string_pretty(&doc).unwrap())
+}
+
+pub fn load_index(output: &Output, name: &str) -> Result<Option<Index>, Error> {
+    let path = format!("{}/index.json", output.dir(name));
+    if !Path::new(&path).exists() {
+        return Ok(None);
+    }
+    Ok(Some(parse_json(&path, &read_file(&path)?)?))
+}
+
+pub fn write_index(output: &Output, name: &str, index: &Index) -> Result<(), Error> {
+    write_file(&output.dir(name), "index.json", &to_string_pretty(index).unwrap())
+}
+
+pub fn load_params(path: &str) -> Result<Params, Error> {
+    Ok(Worldfile::from_file(path)?.params)
+}
+
+fn read_file(filename: &str) -> Result<String, Error> {
+    let mut file = File::open(filename).map_err(|e| Error::io(filename, e))?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).map_err(|e| Error::io(filename, e))?;
+    Ok(contents)
+}
 
-pub fn write_world_with_log(w: &World) {
-    // We're writing more fields but the loader will ignore them
-    let name = w.name.as_ref().expect("Can't write world without name");
-    let id = Uuid::new_v4().simple().to_string();
+fn parse_json<T: DeserializeOwned>(path: &str, s: &str) -> Result<T, Error> {
+    serde_json::from_str(s).map_err(|e| Error::json(path, e))
+}
+
+// Parses a world file, log file, checkpoint or campaign, upgrading it to the current format.
+fn parse_document<T: DeserializeOwned>(path: &str, s: &str) -> Result<T, Error> {
+    let mut doc: Value = parse_json(path, s)?;
+    migrate(path, &mut doc)?;
+    serde_json::from_value(doc).map_err(|e| Error::json(path, e))
+}
+
+
+// Documents that contain params have a format_version, which is increased whenever a change to
+// the params or the structure of the documents would stop older documents from loading. Older
+// documents are upgraded by running the migrations from their version onwards. Documents without
+// a format_version are version 0.
+
+pub const FORMAT_VERSION: u32 = 3;
+
+const MIGRATIONS: [fn(&mut Value); FORMAT_VERSION as usize] = [
+    migrate_v0_to_v1,
+    migrate_v1_to_v2,
+    migrate_v2_to_v3,
+];
+
+fn migrate(path: &str, doc: &mut Value) -> Result<(), Error> {
+    let version = doc.get("format_version").and_then(Value::as_u64).unwrap_or(0);
+    if version > FORMAT_VERSION as u64 {
+        return Err(Error::UnsupportedVersion { path: path.to_owned(), version });
+    }
+    if doc.is_object() {
+        for migration in &MIGRATIONS[version as usize..] {
+            migration(doc);
+        }
+        doc["format_version"] = Value::from(FORMAT_VERSION);
+    }
+    Ok(())
+}
+
+// Version 0 documents may be from before view_distance, ring_count, ring_size and
+// checkpoint_interval were added to the params. Missing values are set to the values used by
+// the default campaign at the time, and periodic checkpoints are disabled.
+fn migrate_v0_to_v1(doc: &mut Value) {
+    let defaults = [("view_distance", 6), ("ring_count", 2), ("ring_size", 3), ("checkpoint_interval", 0)];
+    add_param_defaults(doc, &defaults);
+}
+
+// Version 1 documents are from before the census was added. They need no changes, because a
+// missing census_size defaults to 0, which records no census and keeps their logs as they were.
+fn migrate_v1_to_v2(_doc: &mut Value) {}
+
+// Version 2 checkpoints are from before creatures had ids. The creatures are numbered in their
+// processing order; their parents are unknown and they are treated as generation 0.
+fn migrate_v2_to_v3(doc: &mut Value) {
+    if let Some(creatures) = doc.get_mut("creatures").and_then(Value::as_array_mut) {
+        for (i, entry) in creatures.iter_mut().enumerate() {
+            if let Some(creature) = entry.get_mut(1).and_then(Value::as_object_mut) {
+                creature.insert("id".to_owned(), Value::from(i as u64 + 1));
+            }
+        }
+        let next_id = creatures.len() as u64 + 1;
+        doc["next_creature_id"] = Value::from(next_id);
+    }
+}
+
+fn add_param_defaults(doc: &mut Value, defaults: &[(&str, u64)]) {
+    if let Some(params) = doc.get_mut("params").and_then(Value::as_object_mut) {
+        for (field, value) in defaults {
+            params.entry(*field).or_insert_with(|| Value::from(*value));
+        }
+    }
+}
+
+
+#[derive(Deserialize)]
+struct Checkpoint {
+    id: String,
+    name: Option<String>,
+    params: Params,
+    random: RNG,
+    cycle: u64,
+    next_creature_id: u64,
+    creatures: Vec<((u32, u32), Creature)>,
+    plants: Vec<((u32, u32), Plant)>,
+    log: Log,
+    #[serde(default)]
+    sim_num: Option<u32>,
+    #[serde(default)]
+    sweep_point: Option<SweepPoint>,
+    #[serde(default)]
+    output: Output,
+}
+
+impl Checkpoint {
+    pub fn from_str(s: &str) -> Result<Checkpoint, Error> {
+        parse_document("<string>", s)
+    }
+
+    pub fn from_file(filename: &str) -> Result<Checkpoint, Error> {
+        parse_document(filename, &read_file(filename)?)
+    }
+
+    pub fn into_world(self) -> Result<World, Error> {
+        let mut w = World::new("", self.params)?;
+        w.id = self.id;
+        w.name = self.name;
+        w.random = self.random;
+        w.cycle = self.cycle;
+        w.next_creature_id = self.next_creature_id;
+        w.log = self.log;
+        w.sim_num = self.sim_num;
+        w.sweep_point = self.sweep_point;
+        w.output = self.output;
+        // creatures must be added in their original processing order
+        for (pos, c) in self.creatures {
+            w.terrain.set_creature_at(Some(c), pos);
+        }
+        for (pos, p) in self.plants {
+            w.terrain.set_plant_at(Some(p), pos);
+        }
+        Ok(w)
+    }
+}
+
+pub fn load_checkpoint(path: &str) -> Result<World, Error> {
+    println!("Resuming world from {}", path);
+    Checkpoint::from_file(path)?.into_world()
+}
+
+pub fn world_from_checkpoint_str(s: &str) -> Result<World, Error> {
+    Checkpoint::from_str(s)?.into_world()
+}
+
+pub fn checkpoint_to_string(w: &World) -> String {
     let json = json!({
+        "format_version": FORMAT_VERSION,
+        "id": w.id,
+        "name": w.name,
         "params": w.params,
-        "seed": w.random.seed(),
-        "cycles": w.cycle,
-        "status": ({ if w.num_creatures() > 1 { "ENDOK" } else { "ENDAB" } }),
-        "id": id,
-        "x-log": w.log,
+        "random": w.random,
+        "cycle": w.cycle,
+        "next_creature_id": w.next_creature_id,
+        "creatures": w.terrain.all_creatures_with_pos(),
+        "plants": w.terrain.all_plants_with_pos(),
+        "log": w.log,
+        "sim_num": w.sim_num,
+        "sweep_point": w.sweep_point,
+        "output": w.output,
     });
-    write_worldfile(name, &id, &to_string_pretty(&json).unwrap());
+    serde_json::to_string(&json).unwrap()
+}
+
+pub fn write_checkpoint(w: &World) -> Result<(), Error> {
+    let filename = format!("checkpoint-{}-{}.json", w.id, w.cycle);
+    write_file(&output_dir(w), &filename, &checkpoint_to_string(w))
 }
 
-const OUTPUT_DIR: &str = "output";
+// Rolling checkpoints are overwritten as the world progresses and removed when the result
+// has been written. If one exists the run that wrote it did not finish. Their names contain a
+// key made from the seed the world started with and its params, so that a run only resumes
+// a checkpoint written by a run of the same world.
+
+const ROLLING_SUFFIX: &str = "-latest.json";
+
+pub fn run_key(w: &World) -> String {
+    let text = format!("{:?} {}", w.random.seed(), serde_json::to_string(&w.params).unwrap());
+    format!("{:016x}", utils::fnv1a(text.as_bytes()))
+}
+
+fn rolling_checkpoint_filename(w: &World) -> String {
+    format!("checkpoint-{}-{}{}", w.id, run_key(w), ROLLING_SUFFIX)
+}
+
+pub fn write_rolling_checkpoint(w: &World) -> Result<(), Error> {
+    write_file(&output_dir(w), &rolling_checkpoint_filename(w), &checkpoint_to_string(w))
+}
+
+pub fn remove_rolling_checkpoint(w: &World) {
+    let _ = fs::remove_file(format!("{}/{}", output_dir(w), rolling_checkpoint_filename(w)));
+}
 
-pub fn write_worldfile(name: &str, id: &str, text: &str) {
-    let path = format!("{}/{}", OUTPUT_DIR, name);
-    fs::create_dir_all(&path).expect("Unable to create output directory");
-    let filename = format!("{}/log-{}.json", &path, id);
-    let data = text.as_bytes();
-    let mut file = File::create(&filename).expect(&format!("Unable to create file {}", &filename));
-    file.write_all(data).expect("Write error");
-    file.sync_data().expect("Sync data error");
+// Splits the rolling checkpoints next to the world's results into those of unfinished runs of
+// the world, newest first, and those of other runs.
+pub fn find_rolling_checkpoints_of(w: &World) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let suffix = format!("-{}{}", run_key(w), ROLLING_SUFFIX);
+    find_rolling_checkpoints(&w.output, w.name.as_deref().unwrap_or(""))
+        .into_iter()
+        .partition(|p| p.file_name().and_then(|f| f.to_str()).is_some_and(|f| f.ends_with(&suffix)))
 }
 
+pub fn find_rolling_checkpoints(output: &Output, name: &str) -> Vec<PathBuf> {
+    let entries = match fs::read_dir(output.dir(name)) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut found: Vec<(std::time::SystemTime, PathBuf)> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.file_name().and_then(|f| f.to_str()).is_some_and(|f|
+            f.starts_with("checkpoint-") && f.ends_with(ROLLING_SUFFIX)))
+        .filter_map(|p| fs::metadata(&p).and_then(|m| m.modified()).ok().map(|t| (t, p)))
+        .collect();
+    // newest first
+    found.sort_by_key(|(t, _)| std::cmp::Reverse(*t));
+    found.into_iter().map(|(_, p)| p).collect()
+}
+
+
+pub fn write_result(w: &World) -> Result<(), Error> {
+    let text = to_string_pretty(&RunResult::from_world(w)).unwrap();
+    write_file(&output_dir(w), &w.output.result_filename(w), &text)
+}
+
+
+// Results are written to a directory named after the world, or the campaign, below the output
+// root. By default result files are named after the world's id. Naming them by simulation number
+// and seed instead makes the names of a seeded campaign's results predictable.
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Output {
+    pub root: String,
+    pub naming: Naming,
+    pub log_sink: LogSinkKind,
+    pub genealogy: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Naming {
+    Id,
+    SimNumber,
+}
+
+impl Default for Output {
+    fn default() -> Output {
+        Output { root: "output".to_owned(), naming: Naming::Id, log_sink: LogSinkKind::Memory, genealogy: false }
+    }
+}
+
+// Options given on the command line, which replace those in a campaign or checkpoint.
+#[derive(Default)]
+pub struct OutputOptions {
+    pub root: Option<String>,
+    pub log_sink: Option<LogSinkKind>,
+    pub genealogy: bool,
+}
+
+impl OutputOptions {
+    pub fn apply_to(&self, output: &mut Output) {
+        if let Some(root) = &self.root {
+            output.root = root.clone();
+        }
+        if let Some(log_sink) = self.log_sink {
+            output.log_sink = log_sink;
+        }
+        if self.genealogy {
+            output.genealogy = true;
+        }
+    }
+}
+
+impl Output {
+    pub fn dir(&self, name: &str) -> String {
+        format!("{}/{}", self.root, name)
+    }
+
+    pub fn result_filename(&self, w: &World) -> String {
+        match (self.naming, w.sim_num) {
+            (Naming::SimNumber, Some(n)) => {
+                let seed: String = w.random.seed().iter().map(|s| format!("{:08x}", s)).collect();
+                format!("{}-{}.json", n, seed)
+            }
+            _ => format!("log-{}.json", w.id),
+        }
+    }
+
+    pub fn log_filename(&self, w: &World) -> Option<String> {
+        let result = self.result_filename(w);
+        self.log_sink.extension().map(|ext| format!("{}.{}", result.trim_end_matches(".json"), ext))
+    }
+
+    pub fn genealogy_filename(&self, w: &World) -> Option<String> {
+        let result = self.result_filename(w);
+        self.genealogy.then(|| format!("{}.genealogy", result.trim_end_matches(".json")))
+    }
+}
+
+pub fn log_file_path(w: &World) -> Option<String> {
+    w.output.log_filename(w).map(|f| format!("{}/{}", output_dir(w), f))
+}
+
+pub fn genealogy_file_path(w: &World) -> Option<String> {
+    w.output.genealogy_filename(w).map(|f| format!("{}/{}", output_dir(w), f))
+}
+
+fn output_dir(w: &World) -> String {
+    w.output.dir(w.name.as_ref().expect("Can't write world without name"))
+}
+
+pub fn write_file(path: &str, basename: &str, text: &str) -> Result<(), Error> {
+    fs::create_dir_all(path).map_err(|e| Error::io(path, e))?;
+    let filename = format!("{}/{}", path, basename);
+    // write to a temporary file first so that a killed job never leaves a truncated file
+    let tmp_filename = format!("{}.tmp", &filename);
+    let mut file = File::create(&tmp_filename).map_err(|e| Error::io(&tmp_filename, e))?;
+    file.write_all(text.as_bytes()).map_err(|e| Error::io(&tmp_filename, e))?;
+    file.sync_data().map_err(|e| Error::io(&tmp_filename, e))?;
+    fs::rename(&tmp_filename, &filename).map_err(|e| Error::io(&filename, e))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink;
+
+    fn small_world() -> World {
+        let mut params = Params::for_testing();
+        params.world_size = 40;
+        params.start_pop_size = 60;
+        params.start_plant_count = 300;
+        let mut w = World::new("test", params).unwrap();
+        w.random = RNG::from_seed(&[1, 2, 3, 4]);
+        w.add_initial_plants_and_creatures();
+        w
+    }
+
+    #[test]
+    fn worldfile_without_seed_reports_missing_field() {
+        let s = format!("{{ \"params\": {} }}", serde_json::to_string(&Params::for_testing()).unwrap());
+        match parse_document::<Worldfile>("world.json", &s) {
+            Err(Error::MissingField { field, .. }) => assert_eq!("seed", field),
+            _ => panic!("expected missing field error"),
+        }
+    }
+
+    #[test]
+    fn unversioned_worldfile_gets_defaults_for_newer_params() {
+        let mut params = serde_json::to_value(Params::for_testing()).unwrap();
+        for field in ["view_distance", "ring_count", "ring_size", "checkpoint_interval", "census_size"].iter() {
+            params.as_object_mut().unwrap().remove(*field);
+        }
+        let s = json!({ "params": params, "seed": [1, 2, 3, 4] }).to_string();
+
+        let wf: Worldfile = parse_document("world.json", &s).unwrap();
+
+        assert_eq!((6, 2, 3, 0, 0), (wf.params.view_distance, wf.params.ring_count, wf.params.ring_size,
+                                     wf.params.checkpoint_interval, wf.params.census_size));
+    }
+
+    #[test]
+    fn rejects_documents_from_newer_versions() {
+        let s = json!({ "format_version": FORMAT_VERSION + 1, "params": {}, "seed": [1, 2, 3, 4] }).to_string();
+        match parse_document::<Worldfile>("world.json", &s) {
+            Err(Error::UnsupportedVersion { version, .. }) => assert_eq!(FORMAT_VERSION as u64 + 1, version),
+            _ => panic!("expected unsupported version error"),
+        }
+    }
+
+    #[test]
+    fn load_world_reports_missing_file() {
+        match load_world("does-not-exist.json") {
+            Err(Error::Io { path, .. }) => assert_eq!("does-not-exist.json", path),
+            _ => panic!("expected I/O error"),
+        }
+    }
+
+    #[test]
+    fn result_round_trip_preserves_log() {
+        let mut w = small_world();
+        w.do_cycles(2000);
+        w.log.add_entry(w.cycle);
+        w.log.set_num_creatures(w.num_creatures());
+
+        let text = to_string_pretty(&RunResult::from_world(&w)).unwrap();
+        let result: RunResult = parse_json("log.json", &text).unwrap();
+
+        assert_eq!(FORMAT_VERSION, result.format_version);
+        assert_eq!((w.id.as_str(), w.cycle, w.status()), (result.id.as_str(), result.cycles, result.status.as_str()));
+        assert_eq!(w.random.seed(), &result.seed);
+        assert_eq!(Some(w.num_creatures()), result.log.entries[0].num_creatures());
+        assert_eq!(text, to_string_pretty(&result).unwrap());
+        // a result can be used as a world file
+        assert_eq!(result.seed, parse_json::<Worldfile>("log.json", &text).unwrap().seed);
+    }
+
+    #[test]
+    fn streamed_log_is_read_back_with_result() {
+        let dir = std::env::temp_dir().join(format!("crellinor-test-{}", std::process::id()));
+        let mut w = small_world();
+        w.output.root = dir.to_str().unwrap().to_owned();
+        w.output.log_sink = LogSinkKind::Ndjson;
+        let mut sink = sink::open(w.output.log_sink, log_file_path(&w).as_deref(), None).unwrap();
+        for cycle in [10_000, 10_100, 10_200].iter() {
+            w.log.add_entry(*cycle);
+            w.log.set_num_creatures(*cycle as u32);
+            sink.write_entry(&w.log.take_all()[0]).unwrap();
+        }
+        w.cycle = 10_200;
+        write_result(&w).unwrap();
+
+        let path = format!("{}/{}", output_dir(&w), w.output.result_filename(&w));
+        let result = load_result(&path).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        let cycles: Vec<u64> = result.log.entries.iter().map(|e| e.cycle()).collect();
+        assert_eq!(vec![10_000, 10_100, 10_200], cycles);
+        assert_eq!(Some(10_100), result.log.entries[1].num_creatures());
+    }
+
+    #[test]
+    fn checkpoint_round_trip_preserves_world() {
+        let mut w = small_world();
+        w.do_cycles(500);
+
+        let cp = checkpoint_to_string(&w);
+        let restored = world_from_checkpoint_str(&cp).unwrap();
+
+        assert_eq!(w.id, restored.id);
+        assert_eq!(w.cycle, restored.cycle);
+        assert_eq!(w.next_creature_id, restored.next_creature_id);
+        assert_eq!(w.num_creatures(), restored.num_creatures());
+        assert_eq!(cp, checkpoint_to_string(&restored));
+    }
+
+    #[test]
+    fn creatures_in_old_checkpoints_are_numbered() {
+        let w = small_world();
+        let mut doc: Value = serde_json::from_str(&checkpoint_to_string(&w)).unwrap();
+        doc["format_version"] = Value::from(2);
+        doc.as_object_mut().unwrap().remove("next_creature_id");
+        for entry in doc["creatures"].as_array_mut().unwrap() {
+            entry[1].as_object_mut().unwrap().remove("id");
+        }
+
+        let restored = world_from_checkpoint_str(&doc.to_string()).unwrap();
+
+        let mut ids: Vec<u64> = restored.terrain.all_creatures().iter().map(|c| c.id).collect();
+        ids.sort_unstable();
+        assert_eq!((1..=w.num_creatures() as u64).collect::<Vec<u64>>(), ids);
+        assert_eq!(w.num_creatures() as u64 + 1, restored.next_creature_id);
+    }
+
+    #[test]
+    fn rolling_checkpoint_is_only_found_by_runs_of_the_same_world() {
+        let dir = std::env::temp_dir().join(format!("crellinor-test-rolling-{}", std::process::id()));
+        let mut w = small_world();
+        w.output.root = dir.to_str().unwrap().to_owned();
+        w.do_cycles(100);
+        write_rolling_checkpoint(&w).unwrap();
+
+        let mut same = small_world();
+        same.output = w.output.clone();
+        let mut other_seed = small_world();
+        other_seed.output = w.output.clone();
+        other_seed.random = RNG::from_seed(&[4, 3, 2, 1]);
+        let mut other_params = small_world();
+        other_params.output = w.output.clone();
+        other_params.params.eat_ep += 1;
+        let counts = |w: &World| {
+            let (of_world, others) = find_rolling_checkpoints_of(w);
+            (of_world.len(), others.len())
+        };
+        let found = [counts(&same), counts(&other_seed), counts(&other_params)];
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!([(1, 0), (0, 1), (0, 1)], found);
+    }
+
+    #[test]
+    fn resumed_world_continues_exactly_like_original() {
+        let mut w = small_world();
+        w.do_cycles(500);
+        let mut restored = world_from_checkpoint_str(&checkpoint_to_string(&w)).unwrap();
+
+        w.do_cycles(2000);
+        restored.do_cycles(2000);
+
+        assert_eq!(checkpoint_to_string(&w), checkpoint_to_string(&restored));
+    }
+}
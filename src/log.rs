@@ -1,31 +1,78 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use serde_derive::*;
+use serde_json::Value;
 use crate::creature::Creature;
+use crate::diversity::Diversity;
+use crate::genealogy::Record;
+use crate::plant::Plant;
 use crate::program::Instr;
+use crate::utils::ordered_map;
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Default, Clone)]
 pub struct Log {
     pub entries: Vec<LogEntry>,
     pub total_cycles: u64,
+    // when muted the instructions aren't counted
+    #[serde(skip)]
+    pub muted: bool,
+    // the genealogy records since they were last written, if a genealogy is kept
+    #[serde(skip)]
+    pub genealogy: Option<Vec<Record>>,
 }
 
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct LogEntry {
     cycle: u64,
     num_creatures: Option<u32>,
+    // the state of the world at the cycle of the entry
+    num_plants: Option<u32>,
+    plant_ep: Option<u64>,
+    creature_ep: Option<u64>,
+    mean_age: Option<f64>,
+    median_age: Option<f64>,
+    max_age: Option<u64>,
+    mean_generation: Option<f64>,
+    max_generation: Option<u32>,
+    mean_ring_count: Option<f64>,
+    // what happened in the interval that starts with the entry; every successful mating
+    // produces one offspring, so births is also the number of successful matings
+    #[serde(default)]
+    births: u32,
+    #[serde(default)]
+    deaths_starved: u32,
+    #[serde(default)]
+    deaths_old_age: u32,
+    #[serde(default)]
+    mating_attempts: u32,
+    #[serde(default)]
+    mutations: u32,
+    diversity: Option<Diversity>,
+    // the most common programs at the cycle of the entry, if a census is taken
+    census: Option<Vec<CensusEntry>>,
     num_programs: Option<u32>,
-    programs: Option<HashMap<String, u32>>,
+    programs: Option<BTreeMap<String, u32>>,
+    #[serde(serialize_with = "ordered_map")]
     instr_count: HashMap<Instr, u32>
 }
 
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CensusEntry {
+    pub program: String,
+    pub count: u32,
+    pub mean_ep: f64,
+}
+
+
 
 impl Log {
     pub fn new() -> Log {
         Log {
             entries: Vec::new(),
             total_cycles: 0,
+            muted: false,
+            genealogy: None,
         }
     }
 
@@ -34,13 +81,47 @@ impl Log {
             LogEntry {
                 cycle,
                 num_creatures: None,
+                num_plants: None,
+                plant_ep: None,
+                creature_ep: None,
+                mean_age: None,
+                median_age: None,
+                max_age: None,
+                mean_generation: None,
+                max_generation: None,
+                mean_ring_count: None,
+                births: 0,
+                deaths_starved: 0,
+                deaths_old_age: 0,
+                mating_attempts: 0,
+                mutations: 0,
+                diversity: None,
+                census: None,
                 num_programs: None,
                 programs: None,
                 instr_count: HashMap::new()
             });
     }
 
-    fn set<F>(&mut self, changefn: F) where F: Fn(&mut LogEntry) {
+    pub fn last_cycle(&self) -> Option<u64> {
+        self.entries.last().map(|e| e.cycle)
+    }
+
+    pub fn first_cycle(&self) -> Option<u64> {
+        self.entries.first().map(|e| e.cycle)
+    }
+
+    // Removes the complete entries, i.e. all but the last one, which is still being written to.
+    pub fn take_completed(&mut self) -> Vec<LogEntry> {
+        let n = self.entries.len().saturating_sub(1);
+        self.entries.drain(..n).collect()
+    }
+
+    pub fn take_all(&mut self) -> Vec<LogEntry> {
+        self.entries.drain(..).collect()
+    }
+
+    fn set<F>(&mut self, changefn: F) where F: FnOnce(&mut LogEntry) {
         if let Some(entry) = self.entries.last_mut() {
             changefn(entry);
         }
@@ -50,10 +131,87 @@ impl Log {
         self.set(|e| e.num_creatures = Some(n));
     }
 
+    pub fn set_population(&mut self, creatures: &[&Creature], plants: &[&Plant]) {
+        let mut ages: Vec<u64> = creatures.iter().map(|c| c.age()).collect();
+        ages.sort_unstable();
+        let n = ages.len();
+        let mean_age = if n > 0 { Some(ages.iter().sum::<u64>() as f64 / n as f64) } else { None };
+        let median_age = match n {
+            0 => None,
+            _ if n % 2 == 1 => Some(ages[n / 2] as f64),
+            _ => Some((ages[n / 2 - 1] + ages[n / 2]) as f64 / 2.0),
+        };
+        let generation_sum: u64 = creatures.iter().map(|c| c.generation as u64).sum();
+        let mean_generation = if n > 0 { Some(generation_sum as f64 / n as f64) } else { None };
+        let max_generation = creatures.iter().map(|c| c.generation).max();
+        let ring_count_sum: usize = creatures.iter().map(|c| c.num_rings()).sum();
+        let mean_ring_count = if n > 0 { Some(ring_count_sum as f64 / n as f64) } else { None };
+        let plant_ep = plants.iter().map(|p| p.ep as u64).sum();
+        let creature_ep = creatures.iter().map(|c| c.ep as u64).sum();
+        self.set(|e| {
+            e.num_plants = Some(plants.len() as u32);
+            e.plant_ep = Some(plant_ep);
+            e.creature_ep = Some(creature_ep);
+            e.mean_age = mean_age;
+            e.median_age = median_age;
+            e.max_age = ages.last().copied();
+            e.mean_generation = mean_generation;
+            e.max_generation = max_generation;
+            e.mean_ring_count = mean_ring_count;
+        });
+    }
+
+    pub fn set_diversity(&mut self, diversity: Diversity) {
+        self.set(|e| e.diversity = Some(diversity));
+    }
+
+    // Records the size most common programs of all creatures, most common first. Programs that
+    // are equally common are ordered by their text so that the census doesn't depend on the
+    // order of the creatures.
+    pub fn set_census(&mut self, creatures: &[&Creature], size: usize) {
+        let mut programs: BTreeMap<String, (u32, u64)> = BTreeMap::new();
+        for c in creatures {
+            let (count, ep) = programs.entry(c.pp_program()).or_insert((0, 0));
+            *count += 1;
+            *ep += c.ep as u64;
+        }
+        let mut census: Vec<CensusEntry> = programs.into_iter()
+            .map(|(program, (count, ep))| CensusEntry { program, count, mean_ep: ep as f64 / count as f64 })
+            .collect();
+        census.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.program.cmp(&b.program)));
+        census.truncate(size);
+        self.set(|e| e.census = Some(census));
+    }
+
+    pub fn record<F>(&mut self, record: F) where F: FnOnce() -> Record {
+        if let Some(records) = self.genealogy.as_mut() {
+            records.push(record());
+        }
+    }
+
+    pub fn count_birth(&mut self) {
+        self.set(|e| e.births += 1);
+    }
+
+    pub fn count_death(&mut self, cause: Death) {
+        match cause {
+            Death::Starvation => self.set(|e| e.deaths_starved += 1),
+            Death::OldAge => self.set(|e| e.deaths_old_age += 1),
+        }
+    }
+
+    pub fn count_mating_attempt(&mut self) {
+        self.set(|e| e.mating_attempts += 1);
+    }
+
+    pub fn count_mutations(&mut self, n: u32) {
+        self.set(|e| e.mutations += n);
+    }
+
     pub fn set_programs(&mut self, creatures: Vec<&Creature>) {
-        let mut programs = HashMap::new();
-        for i in 0..creatures.len() {
-            let p = creatures[i].pp_program();
+        let mut programs = BTreeMap::new();
+        for c in creatures {
+            let p = c.pp_program();
             let mut count = 1;
             if let Some(n) = programs.get(&p) {
                 count += n;
@@ -65,6 +223,9 @@ impl Log {
     }
 
     pub fn incr_instr_counter(&mut self, instr: Instr) {
+        if self.muted {
+            return;
+        }
         self.set(|e| {
             let mut count = 1;
             if let Some(n) = e.instr_count.get(&instr) {
@@ -77,11 +238,113 @@ impl Log {
 }
 
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Death {
+    Starvation,
+    OldAge,
+}
+
+
+impl LogEntry {
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+
+    pub fn num_creatures(&self) -> Option<u32> {
+        self.num_creatures
+    }
+
+    pub fn num_plants(&self) -> Option<u32> {
+        self.num_plants
+    }
+
+    pub fn plant_ep(&self) -> Option<u64> {
+        self.plant_ep
+    }
+
+    pub fn creature_ep(&self) -> Option<u64> {
+        self.creature_ep
+    }
+
+    pub fn mean_age(&self) -> Option<f64> {
+        self.mean_age
+    }
+
+    pub fn median_age(&self) -> Option<f64> {
+        self.median_age
+    }
+
+    pub fn max_age(&self) -> Option<u64> {
+        self.max_age
+    }
+
+    pub fn mean_generation(&self) -> Option<f64> {
+        self.mean_generation
+    }
+
+    pub fn max_generation(&self) -> Option<u32> {
+        self.max_generation
+    }
+
+    pub fn mean_ring_count(&self) -> Option<f64> {
+        self.mean_ring_count
+    }
+
+    pub fn births(&self) -> u32 {
+        self.births
+    }
+
+    pub fn deaths_starved(&self) -> u32 {
+        self.deaths_starved
+    }
+
+    pub fn deaths_old_age(&self) -> u32 {
+        self.deaths_old_age
+    }
+
+    pub fn mating_attempts(&self) -> u32 {
+        self.mating_attempts
+    }
+
+    pub fn mutations(&self) -> u32 {
+        self.mutations
+    }
+
+    pub fn diversity(&self) -> Option<&Diversity> {
+        self.diversity.as_ref()
+    }
+
+    pub fn census(&self) -> Option<&[CensusEntry]> {
+        self.census.as_deref()
+    }
+
+    pub fn instr_count(&self, instr: Instr) -> u32 {
+        self.instr_count.get(&instr).copied().unwrap_or(0)
+    }
+
+    // Describes the values that differ in an entry replayed from this one. Values this entry
+    // doesn't have, e.g. because it was written by an older version, aren't compared.
+    pub fn differences(&self, replayed: &LogEntry) -> Vec<String> {
+        let recorded = serde_json::to_value(self).unwrap();
+        let replayed = serde_json::to_value(replayed).unwrap();
+        let mut differences = Vec::new();
+        for (field, value) in recorded.as_object().unwrap() {
+            let replayed_value = replayed.get(field).unwrap_or(&Value::Null);
+            if !value.is_null() && value != replayed_value {
+                differences.push(format!("{} was {} but is {}", field, value, replayed_value));
+            }
+        }
+        differences
+    }
+}
+
+
 
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::params::Params;
 
     #[test]
     fn adds_values_to_last_entry() {
@@ -107,4 +370,86 @@ mod tests {
 
         assert_eq!(2, log.entries[0].instr_count[&Instr::MOV]);
     }
+
+    #[test]
+    fn records_population_statistics() {
+        let params = Params::for_testing();
+        let ages = [10, 40, 20, 30];
+        let creatures: Vec<Creature> = ages.iter().map(|a| {
+            let mut c = Creature::new(vec![Instr::NOP; params.ring_size * (*a as usize / 10)], &params);
+            c.bcycle = 100;
+            c.lastprocd = 100 + a;
+            c.generation = (a / 10) as u32;
+            c
+        }).collect();
+        let plants = [Plant::with_ep(5), Plant::with_ep(7)];
+        let mut log = Log::new();
+        log.add_entry(0);
+
+        log.set_population(&creatures.iter().collect::<Vec<_>>(), &plants.iter().collect::<Vec<_>>());
+        log.count_death(Death::OldAge);
+        log.count_mating_attempt();
+
+        let e = &log.entries[0];
+        assert_eq!((Some(2), Some(12)), (e.num_plants, e.plant_ep));
+        assert_eq!(Some(4 * params.creature_start_ep as u64), e.creature_ep);
+        assert_eq!((Some(25.0), Some(25.0), Some(40)), (e.mean_age, e.median_age, e.max_age));
+        assert_eq!((Some(2.5), Some(4)), (e.mean_generation, e.max_generation));
+        assert_eq!(Some(2.5), e.mean_ring_count);
+        assert_eq!((0, 1, 1), (e.deaths_starved, e.deaths_old_age, e.mating_attempts));
+    }
+
+    #[test]
+    fn census_lists_most_common_programs_first() {
+        let params = Params::for_testing();
+        let programs = [vec![Instr::EAT, Instr::MOV], vec![Instr::MOV, Instr::EAT], vec![Instr::EAT, Instr::MOV],
+            vec![Instr::TUR, Instr::TUR]];
+        let creatures: Vec<Creature> = programs.iter().enumerate().map(|(i, p)| {
+            let mut c = Creature::new(p.clone(), &params);
+            c.ep = 100 * (i as u32 + 1);
+            c
+        }).collect();
+        let mut log = Log::new();
+        log.add_entry(0);
+
+        log.set_census(&creatures.iter().collect::<Vec<_>>(), 2);
+
+        let census = log.entries[0].census().unwrap();
+        assert_eq!(2, census.len());
+        assert_eq!((2, 200.0), (census[0].count, census[0].mean_ep));
+        assert_eq!((1, 200.0), (census[1].count, census[1].mean_ep));
+        assert!(census[1].program.starts_with("MOV EAT"));
+    }
+
+    #[test]
+    fn differences_name_changed_values_and_ignore_missing_ones() {
+        let mut recorded = Log::new();
+        recorded.add_entry(100);
+        recorded.set_num_creatures(12);
+        recorded.count_birth();
+        let mut replayed = Log::new();
+        replayed.add_entry(100);
+        replayed.set_num_creatures(13);
+        replayed.count_birth();
+        replayed.set_programs(vec![]);
+
+        let differences = recorded.entries[0].differences(&replayed.entries[0]);
+
+        assert_eq!(vec!["num_creatures was 12 but is 13"], differences);
+        assert!(replayed.entries[0].differences(&replayed.entries[0]).is_empty());
+    }
+
+    #[test]
+    fn takes_completed_entries_and_keeps_current_one() {
+        let mut log = Log::new();
+        log.add_entry(0);
+        log.add_entry(1);
+        log.add_entry(2);
+
+        let completed = log.take_completed();
+
+        assert_eq!(vec![0, 1], completed.iter().map(|e| e.cycle).collect::<Vec<u64>>());
+        assert_eq!(Some(2), log.last_cycle());
+        assert_eq!(0, log.take_completed().len());
+    }
 }
@@ -1,31 +1,262 @@
 extern crate crellinor;
 
+use std::collections::BTreeMap;
 use std::env;
 use std::process::exit;
-use getopts::Options;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use getopts::{Matches, Options};
+use serde_json::Value;
+use crellinor::{Command, Error};
+use crellinor::genealogy::TreeFormat;
+use crellinor::loader::OutputOptions;
+
+const USAGE: &str = "Usage: crellinor COMMAND [OPTIONS]
+
+Commands:
+    run WORLDFILE         Run a single world. A log file can be used as a world file.
+    resume CHECKPOINT     Resume a world from a checkpoint.
+    campaign [FILE]       Run the campaign described in the file, or the default campaign.
+    serve WORLDFILE       Run a world in the web UI.
+    replay LOGFILE        Rerun the world from a log file and compare with the recorded run.
+    check WORLDFILE       Check the parameters in a world file.
+    analyze [DIR]         Summarise the results below the directory, or below output, in CSV files.
+    genealogy LOGFILE     Export the genealogy of a run as a tree.
+    disasm FILE           Print the program in the file with one ring per line.
+
+Use crellinor COMMAND -h to see the options for a command.";
 
 fn main() {
     let args: Vec<String> = env::args().collect();
+    if args.len() < 2 || args[1] == "-h" || args[1] == "--help" {
+        println!("{}", USAGE);
+        exit(if args.len() < 2 { -1 } else { 0 });
+    }
+
+    let command = match args[1].as_str() {
+        "run" => parse_run(&args[2..]),
+        "resume" => parse_resume(&args[2..]),
+        "campaign" => parse_campaign(&args[2..]),
+        "serve" => parse_serve(&args[2..]),
+        "replay" => parse_replay(&args[2..]),
+        "check" => parse_check(&args[2..]),
+        "analyze" => parse_analyze(&args[2..]),
+        "genealogy" => parse_genealogy(&args[2..]),
+        "disasm" => parse_disasm(&args[2..]),
+        other => {
+            eprintln!("Unknown command {}\n\n{}", other, USAGE);
+            exit(-1);
+        }
+    };
+
+    match crellinor::run(command) {
+        Ok(()) => (),
+        Err(Error::Interrupted) => {
+            println!("Stopped. Run the campaign again to continue.");
+            exit(130);
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            exit(1);
+        }
+    }
+}
+
+
+fn parse_run(args: &[String]) -> Command {
+    let mut opts = options();
+    opts.optopt("s", "seed", "Use this seed instead of the one in the world file.", "A,B,C,D");
+    opts.optmulti("p", "param", "Replace a parameter in the world file. Can be given multiple times.", "NAME=VALUE");
+    opts.optopt("c", "checkpoint-at", "Write a checkpoint when the world reaches the given cycle.", "CYCLE");
+    add_output_opts(&mut opts);
+    let (matches, worldfile) = parse(&opts, args, "run WORLDFILE");
+    Command::Run {
+        worldfile,
+        seed: matches.opt_str("seed").map(|s| parse_seed(&opts, &s)),
+        overrides: parse_overrides(&opts, &matches.opt_strs("param")),
+        checkpoint_at: opt_u64(&opts, &matches, "checkpoint-at", "run WORLDFILE"),
+        output: output_options(&opts, &matches, "run WORLDFILE"),
+    }
+}
+
+fn parse_resume(args: &[String]) -> Command {
+    let mut opts = options();
+    opts.optopt("c", "checkpoint-at", "Write a checkpoint when the world reaches the given cycle.", "CYCLE");
+    add_output_opts(&mut opts);
+    let (matches, checkpoint) = parse(&opts, args, "resume CHECKPOINT");
+    Command::Resume {
+        checkpoint,
+        checkpoint_at: opt_u64(&opts, &matches, "checkpoint-at", "resume CHECKPOINT"),
+        output: output_options(&opts, &matches, "resume CHECKPOINT"),
+    }
+}
+
+fn parse_campaign(args: &[String]) -> Command {
+    let mut opts = options();
+    add_output_opts(&mut opts);
+    let matches = parse_opts(&opts, args, "campaign [FILE]");
+    if matches.free.len() > 1 {
+        fail(&opts, "campaign [FILE]", "Too many arguments");
+    }
+    Command::Campaign {
+        campaignfile: matches.free.first().cloned(),
+        output: output_options(&opts, &matches, "campaign [FILE]"),
+        stop_flag: stop_on_ctrl_c(),
+    }
+}
+
+fn parse_serve(args: &[String]) -> Command {
+    let mut opts = options();
+    opts.optopt("a", "addr", "Listen on this address. Defaults to localhost:3000.", "HOST:PORT");
+    opts.optopt("u", "ui", "Serve the web UI from this directory. Defaults to resources/ui.", "PATH");
+    opts.optflag("r", "resume", "The file is a checkpoint rather than a world file.");
+    let (matches, path) = parse(&opts, args, "serve WORLDFILE");
+    Command::Serve {
+        path,
+        from_checkpoint: matches.opt_present("resume"),
+        addr: matches.opt_str("addr").unwrap_or_else(|| "localhost:3000".to_owned()),
+        ui_path: matches.opt_str("ui").unwrap_or_else(|| "resources/ui".to_owned()),
+    }
+}
+
+fn parse_replay(args: &[String]) -> Command {
+    let opts = options();
+    let (_, logfile) = parse(&opts, args, "replay LOGFILE");
+    Command::Replay { logfile }
+}
+
+fn parse_check(args: &[String]) -> Command {
+    let opts = options();
+    let (_, worldfile) = parse(&opts, args, "check WORLDFILE");
+    Command::Check { worldfile }
+}
+
+fn parse_analyze(args: &[String]) -> Command {
+    let mut opts = options();
+    opts.optflag("r", "per-run", "Also write a CSV file with the log entries of each run.");
+    let matches = parse_opts(&opts, args, "analyze [DIR]");
+    if matches.free.len() > 1 {
+        fail(&opts, "analyze [DIR]", "Too many arguments");
+    }
+    Command::Analyze {
+        dir: matches.free.first().cloned().unwrap_or_else(|| "output".to_owned()),
+        per_run: matches.opt_present("per-run"),
+    }
+}
+
+fn parse_genealogy(args: &[String]) -> Command {
+    let mut opts = options();
+    opts.optopt("f", "format", "Export as newick (following the first parent) or graphml. Defaults to newick.", "FORMAT");
+    let (matches, logfile) = parse(&opts, args, "genealogy LOGFILE");
+    let format = matches.opt_str("format").map_or(TreeFormat::Newick, |s|
+        serde_json::from_value(Value::from(s.clone()))
+            .unwrap_or_else(|_| fail(&opts, "genealogy LOGFILE", &format!("Unknown format {}", s))));
+    Command::Genealogy { logfile, format }
+}
+
+fn parse_disasm(args: &[String]) -> Command {
+    let mut opts = options();
+    opts.optopt("r", "ring-size", "The number of instructions in a ring. Defaults to 3.", "SIZE");
+    let (matches, file) = parse(&opts, args, "disasm FILE");
+    let ring_size = opt_u64(&opts, &matches, "ring-size", "disasm FILE").unwrap_or(3) as usize;
+    if ring_size == 0 {
+        fail(&opts, "disasm FILE", "The ring size must be greater than 0");
+    }
+    Command::Disasm { file, ring_size }
+}
+
+
+// helper functions
 
+// The first Ctrl-C asks the campaign to stop gracefully, the second quits immediately.
+fn stop_on_ctrl_c() -> Arc<AtomicBool> {
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    let handler_flag = Arc::clone(&stop_flag);
+    let installed = ctrlc::set_handler(move || {
+        if handler_flag.swap(true, Ordering::SeqCst) {
+            exit(130);
+        }
+        println!("Stopping. Waiting for running worlds to write their results; press Ctrl-C again to quit immediately.");
+    });
+    if let Err(e) = installed {
+        eprintln!("Error: cannot install signal handler: {}", e);
+        exit(1);
+    }
+    stop_flag
+}
+
+fn parse(opts: &Options, args: &[String], synopsis: &str) -> (Matches, String) {
+    let matches = parse_opts(opts, args, synopsis);
+    if matches.free.len() != 1 {
+        fail(opts, synopsis, "Expected exactly one file");
+    }
+    let path = matches.free[0].clone();
+    (matches, path)
+}
+
+fn options() -> Options {
     let mut opts = Options::new();
-    opts.optopt("f", "file", "Load world from file. If you use a log file the simulation will restart from the beginning.", "PATH");
-    opts.optflag("w", "web", "Run the web server. A world file must be given.");
     opts.optflag("h", "help", "Display this help message");
+    opts
+}
 
-    let matches = match opts.parse(&args[1..]) {
-        Ok(m) => { m }
-        Err(f) => {
-            print!("{}", opts.usage(&f.to_string()));
-            exit(-1);
-        }
+fn parse_opts(opts: &Options, args: &[String], synopsis: &str) -> Matches {
+    let matches = match opts.parse(args) {
+        Ok(m) => m,
+        Err(f) => fail(opts, synopsis, &f.to_string()),
     };
     if matches.opt_present("help") {
-        print!("{}", opts.usage(&"Usage: crellinor [OPTIONS]"));
+        print!("{}", opts.usage(&format!("Usage: crellinor {} [OPTIONS]", synopsis)));
         exit(0);
     }
+    matches
+}
+
+// The options replace the settings in a campaign file or a checkpoint.
+fn add_output_opts(opts: &mut Options) {
+    opts.optopt("o", "output", "Write results below this directory. Defaults to output.", "DIR");
+    opts.optopt("l", "log-sink", "Where to write log entries: memory (with the result), ndjson or csv (streamed to a file), or null (nowhere).", "SINK");
+    opts.optflag("g", "genealogy", "Record every birth and death in a genealogy file next to the result.");
+}
+
+fn output_options(opts: &Options, matches: &Matches, synopsis: &str) -> OutputOptions {
+    let log_sink = matches.opt_str("log-sink").map(|s|
+        serde_json::from_value(Value::from(s.clone()))
+            .unwrap_or_else(|_| fail(opts, synopsis, &format!("Unknown log sink {}", s))));
+    OutputOptions { root: matches.opt_str("output"), log_sink, genealogy: matches.opt_present("genealogy") }
+}
+
+fn opt_u64(opts: &Options, matches: &Matches, name: &str, synopsis: &str) -> Option<u64> {
+    match matches.opt_get::<u64>(name) {
+        Ok(v) => v,
+        Err(f) => fail(opts, synopsis, &f.to_string()),
+    }
+}
+
+fn parse_seed(opts: &Options, s: &str) -> [u32; 4] {
+    let parts: Vec<u32> = s.split(',').filter_map(|p| p.trim().parse().ok()).collect();
+    if parts.len() != 4 {
+        fail(opts, "run WORLDFILE", &format!("Invalid seed {}; expected four numbers", s));
+    }
+    [parts[0], parts[1], parts[2], parts[3]]
+}
 
-    let worldfile = matches.opt_str("file");
-    let run_web = matches.opt_present("web");
+fn parse_overrides(opts: &Options, args: &[String]) -> BTreeMap<String, Value> {
+    let mut overrides = BTreeMap::new();
+    for arg in args {
+        match arg.split_once('=') {
+            Some((name, value)) => {
+                // values that aren't valid JSON, e.g. unquoted words, are taken as strings
+                let v = serde_json::from_str(value).unwrap_or_else(|_| Value::from(value));
+                overrides.insert(name.to_owned(), v);
+            }
+            None => fail(opts, "run WORLDFILE", &format!("Invalid parameter {}; expected NAME=VALUE", arg)),
+        }
+    }
+    overrides
+}
 
-    crellinor::run(worldfile, run_web);
+fn fail(opts: &Options, synopsis: &str, message: &str) -> ! {
+    eprint!("{}", opts.usage(&format!("{}\n\nUsage: crellinor {} [OPTIONS]", message, synopsis)));
+    exit(-1);
 }
@@ -1,21 +1,26 @@
-use std::sync::Arc;
-use std::sync::atomic::AtomicU32;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::AtomicBool;
 use core::sync::atomic::Ordering;
 use std::thread;
 use maplit::*;
+use serde_json::Value;
+use crate::campaign::{Campaign, Index, SweepValues};
 use crate::program::Instr::*;
+use crate::error::Error;
+use crate::genetics::Crossover;
+use crate::loader;
+use crate::loader::Output;
 use crate::params::Params;
 use crate::random::RNG;
-use crate::world::World;
 use crate::utils::{round, square};
+use crate::world::World;
 
 
-const NUM_SIMS: u32 = 4000;
-const NUM_THREADS: u32 = 10;
-
+// The campaign that is run when no campaign file is given. It also serves as documentation for
+// how the parameters relate to each other.
 
-fn make_world() -> World {
-    let mut rng = RNG::new();
+pub fn default_campaign() -> Campaign {
 
     // The world is square and world_size gives the length of the square.
     let world_size = 300;
@@ -51,7 +56,7 @@ fn make_world() -> World {
     let creature_max_ep = 4000;
 
     // Creatures start with 1/2 of the maximum EP.
-    let creature_start_ep = creature_max_ep * 1/2;
+    let creature_start_ep = creature_max_ep / 2;
 
     // When creatures mate the initiating parent passes creature_start_ep onto the offspring.
     // Setting the min_mating_ep to 50% above the starting EP ensures the parent has at least 50%
@@ -64,7 +69,7 @@ fn make_world() -> World {
 
     // Empirically, a good value. Keeps successful creatures around for long enough to have a
     // number of chances to mate.
-    let creature_max_age = rng.choose(&[80_000]);
+    let creature_max_age = 80_000;
 
     // Determines how far the creatures' BFA command can see.
     let view_distance = 6;
@@ -73,6 +78,8 @@ fn make_world() -> World {
     let params = Params {
         world_end: 2_000_000,
         log_interval: 10_000,
+        checkpoint_interval: 100_000,
+        census_size: 0,
 
         world_size,
         start_pop_size,
@@ -89,8 +96,17 @@ fn make_world() -> World {
         eat_ep,
         view_distance,
 
-        ring_count: rng.choose(&[2, 3]),
-        ring_size: rng.choose(&[3, 4, 5, 6]),
+        ring_count: 2,
+        ring_size: 3,
+        min_ring_count: None,
+        max_ring_count: None,
+        start_programs: Vec::new(),
+
+        crossover: Crossover::CutAndSplice,
+        point_mutation_rate: 0.0,
+        ring_duplication_rate: 0.0,
+        ring_deletion_rate: 0.0,
+        swap_mutation_rate: 0.0,
 
         instructions: hashmap! {
             EAT => 25,
@@ -105,33 +121,139 @@ fn make_world() -> World {
         },
     };
 
-    World::new("ringstruct", params)
+    // Each world gets one of these values, which allows us to compare program structures.
+    let mut randomize = BTreeMap::new();
+    randomize.insert("ring_count".to_owned(), vec![Value::from(2), Value::from(3)]);
+    randomize.insert("ring_size".to_owned(), (3..=6).map(Value::from).collect());
+
+    Campaign {
+        name: "ringstruct".to_owned(),
+        num_sims: 4000,
+        num_threads: 10,
+        params,
+        randomize,
+        sweep: None,
+        seed: None,
+        output: Output::default(),
+    }
 }
 
 
 // running the worlds
 
-pub fn run() {
-    run_multiverse(make_world);
+// Setting the stop flag stops the campaign gracefully: running worlds write rolling checkpoints
+// and no new worlds are started. The campaign then returns Error::Interrupted.
+
+pub fn run(mut campaign: Campaign, stop_flag: Arc<AtomicBool>) -> Result<(), Error> {
+    campaign.check()?;
+    // An index in the output directory means we are continuing an interrupted campaign. To
+    // reproduce the missing simulations exactly we must use the original seed.
+    let mut index = loader::load_index(&campaign.output, &campaign.name)?.unwrap_or_default();
+    match (campaign.seed, index.seed) {
+        (Some(seed), Some(prev)) if seed != prev =>
+            return Err(Error::Campaign(format!(
+                "output for {} belongs to a campaign with seed {:?}", campaign.name, prev))),
+        (None, Some(prev)) => campaign.seed = Some(prev),
+        (None, None) => campaign.seed = Some(RNG::new_seed()),
+        _ => {}
+    }
+    index.seed = campaign.seed;
+    println!("Campaign seed is {:?}.", campaign.seed.unwrap());
+    // Keeping the campaign with its seed next to the results makes it possible to rerun it.
+    loader::write_campaign(&campaign)?;
+
+    let points = campaign.sweep_points();
+    // Worlds get randomly chosen parameters. Checking one of them catches most mistakes before
+    // thousands of simulations are started.
+    let world = campaign.make_world(0, &points)?;
+    for warning in world.params.validate().warnings {
+        println!("Warning: {}", warning);
+    }
+    let num_runs = campaign.num_runs(&points);
+    if campaign.sweep.is_some() {
+        println!("Sweeping {} points with {} simulations in total.", points.len(), num_runs);
+    }
+
+    // unfinished worlds from an earlier, interrupted run go first
+    let mut jobs = VecDeque::new();
+    for path in loader::find_rolling_checkpoints(&campaign.output, &campaign.name) {
+        let mut world = loader::load_checkpoint(path.to_str().unwrap())?;
+        // the output may have been moved since the checkpoint was written
+        world.output = campaign.output.clone();
+        if !world.sim_num.is_some_and(|n| index.is_finished(n)) {
+            jobs.push_back(Job::Resume(Box::new(world)));
+        }
+    }
+    let resumed: Vec<u32> = jobs.iter().filter_map(|j| match j { Job::Resume(w) => w.sim_num, _ => None }).collect();
+    for sim_num in 0..num_runs {
+        if !index.is_finished(sim_num) && !resumed.contains(&sim_num) {
+            jobs.push_back(Job::Start(sim_num));
+        }
+    }
+    if index.num_finished() > 0 {
+        println!("Continuing campaign; {} simulations have finished already.", index.num_finished());
+    }
+
+    run_multiverse(Arc::new(campaign), Arc::new(points), jobs, index, Arc::clone(&stop_flag));
+    if stop_flag.load(Ordering::Relaxed) {
+        return Err(Error::Interrupted);
+    }
+    println!("Done. Reached the end of all worlds.");
+    Ok(())
 }
 
 
-fn run_multiverse(worldfn: fn() -> World) {
-    let mut handles = Vec::new();
-    let counter = Arc::new(AtomicU32::new(0));
+enum Job {
+    Resume(Box<World>),
+    Start(u32),
+}
 
-    for _ in 0..NUM_THREADS {
-        let thread_counter = Arc::clone(&counter);
+fn run_multiverse(campaign: Arc<Campaign>, points: Arc<Vec<SweepValues>>, jobs: VecDeque<Job>,
+                  index: Index, stop_flag: Arc<AtomicBool>) {
+    let mut handles = Vec::new();
+    let jobs = Arc::new(Mutex::new(jobs));
+    let index = Arc::new(Mutex::new(index));
+
+    for _ in 0..campaign.num_threads {
+        let thread_campaign = Arc::clone(&campaign);
+        let thread_points = Arc::clone(&points);
+        let thread_jobs = Arc::clone(&jobs);
+        let thread_index = Arc::clone(&index);
+        let thread_stop_flag = Arc::clone(&stop_flag);
         let h = thread::spawn(move || {
-            loop {
-                let sim_num = thread_counter.fetch_add(1, Ordering::Relaxed);
-                if sim_num >= NUM_SIMS {
-                    break;
+            while !thread_stop_flag.load(Ordering::Relaxed) {
+                let job = thread_jobs.lock().unwrap().pop_front();
+                let (sim_num, result) = match job {
+                    Some(Job::Resume(mut world)) => {
+                        println!("Resuming simulation #{} at cycle {}.", fmt_sim_num(world.sim_num), world.cycle);
+                        world.stop_flag = Some(Arc::clone(&thread_stop_flag));
+                        (world.sim_num, world.continue_run().map(|_| *world))
+                    }
+                    Some(Job::Start(sim_num)) => {
+                        println!("Starting simulation #{}.", sim_num);
+                        (Some(sim_num), thread_campaign.make_world(sim_num, &thread_points).and_then(|mut world| {
+                            world.stop_flag = Some(Arc::clone(&thread_stop_flag));
+                            world.run()?;
+                            Ok(world)
+                        }))
+                    }
+                    None => break,
+                };
+                match result {
+                    Ok(world) => {
+                        println!("Simulation #{} ended after {} cycles ({}).",
+                                 fmt_sim_num(sim_num), world.cycle, world.status());
+                        if let Some(n) = sim_num {
+                            let mut index = thread_index.lock().unwrap();
+                            index.runs.insert(n, thread_campaign.index_entry(&world));
+                            let c = &thread_campaign;
+                            if let Err(e) = loader::write_index(&c.output, &c.name, &index) {
+                                println!("Failed to update index: {}", e);
+                            }
+                        }
+                    }
+                    Err(e) => println!("Simulation #{} failed: {}", fmt_sim_num(sim_num), e),
                 }
-                println!("Starting simulation #{}.", sim_num);
-                let mut world = worldfn();
-                world.run();
-                println!("Simulation #{} ended after {} cycles.", sim_num, world.cycle);
             }
         });
         handles.push(h);
@@ -140,6 +262,77 @@ fn run_multiverse(worldfn: fn() -> World) {
     while let Some(h) = handles.pop() {
         h.join().unwrap();
     }
-    println!("Done. Reached the end of all worlds.");
 }
 
+fn fmt_sim_num(sim_num: Option<u32>) -> String {
+    sim_num.map_or("?".to_owned(), |n| n.to_string())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::*;
+    use crate::loader::Naming;
+
+    fn small_campaign(root: &str) -> Campaign {
+        let mut params = Params::for_testing();
+        params.world_size = 30;
+        params.start_pop_size = 20;
+        params.start_plant_count = 100;
+        params.world_end = 20_000;
+        params.log_interval = 5_000;
+        Campaign {
+            name: "small".to_owned(), num_sims: 4, num_threads: 2, params, randomize: BTreeMap::new(), sweep: None,
+            seed: Some([1, 2, 3, 4]),
+            output: Output { root: root.to_owned(), naming: Naming::SimNumber, ..Output::default() },
+        }
+    }
+
+    #[test]
+    fn restarted_campaign_only_runs_unfinished_simulations() {
+        let dir = std::env::temp_dir().join(format!("crellinor-test-campaign-{}", std::process::id()));
+        let root = dir.to_str().unwrap();
+        let output = small_campaign(root).output;
+        run(small_campaign(root), Arc::new(AtomicBool::new(false))).unwrap();
+        let mut index = loader::load_index(&output, "small").unwrap().unwrap();
+        assert_eq!(4, index.num_finished());
+        // the results of simulations 0 and 1 are gone, but the index says they have finished
+        let files: Vec<String> = (0..4).map(|n| format!("{}/{}", output.dir("small"), index.runs[&n].file)).collect();
+        for file in &files {
+            fs::remove_file(file).unwrap();
+        }
+        index.runs.remove(&2);
+        index.runs.remove(&3);
+        loader::write_index(&output, "small", &index).unwrap();
+
+        run(small_campaign(root), Arc::new(AtomicBool::new(false))).unwrap();
+        let exists: Vec<bool> = files.iter().map(|f| fs::metadata(f).is_ok()).collect();
+        let num_finished = loader::load_index(&output, "small").unwrap().unwrap().num_finished();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(vec![false, false, true, true], exists);
+        assert_eq!(4, num_finished);
+    }
+
+    #[test]
+    fn stopped_campaign_reports_interruption() {
+        let dir = std::env::temp_dir().join(format!("crellinor-test-stopped-{}", std::process::id()));
+        let campaign = small_campaign(dir.to_str().unwrap());
+        let output = campaign.output.clone();
+
+        let result = run(campaign, Arc::new(AtomicBool::new(true)));
+        let index = loader::load_index(&output, "small").unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert!(matches!(result, Err(Error::Interrupted)));
+        assert_eq!(0, index.map_or(0, |i| i.num_finished()));
+    }
+
+    #[test]
+    fn default_campaign_is_the_ringstruct_campaign() {
+        let file = loader::load_campaign("resources/campaigns/ringstruct.json").unwrap();
+
+        assert_eq!(serde_json::to_value(&file).unwrap(), serde_json::to_value(default_campaign()).unwrap());
+    }
+}
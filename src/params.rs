@@ -1,14 +1,23 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use serde_derive::*;
+use serde_json::Value;
 use maplit::*;
+use crate::error::Error;
+use crate::genetics::Crossover;
+use crate::program;
 use crate::program::Instr;
 use crate::program::Instr::*;
+use crate::utils::ordered_map;
 
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Params {
     pub world_end: u64,
     pub log_interval: u64,
+    #[serde(default)]
+    pub checkpoint_interval: u64,   // 0 disables periodic checkpoints
+    #[serde(default)]
+    pub census_size: usize,         // programs in the census of each log entry; 0 disables it
 
     pub world_size: u32,
     pub start_pop_size: u32,
@@ -26,18 +35,56 @@ pub struct Params {
     pub min_mating_ep: u32,         // should be greater than 2 * creature_start_ep
     pub view_distance: u32,         // high performance impact
 
-    pub ring_count: usize,
+    pub ring_count: usize,          // the number of rings in the programs of the start creatures
     pub ring_size: usize,
+    // when given, ring duplications and deletions change the number of rings in a program
+    // within these bounds; without ring_duplication_rate or ring_deletion_rate the number of
+    // rings can't change
+    #[serde(default)]
+    pub min_ring_count: Option<usize>,
+    #[serde(default)]
+    pub max_ring_count: Option<usize>,
+    // programs for the start creatures in the format read by program::parse, given to them in
+    // turn; when empty the start creatures get the base strategy and random rings
+    #[serde(default)]
+    pub start_programs: Vec<String>,
 
+    #[serde(default)]
+    pub crossover: Crossover,
+    // the probabilities that the program of an offspring is mutated in these ways
+    #[serde(default)]
+    pub point_mutation_rate: f64,
+    #[serde(default)]
+    pub ring_duplication_rate: f64,
+    #[serde(default)]
+    pub ring_deletion_rate: f64,
+    #[serde(default)]
+    pub swap_mutation_rate: f64,
+
+    #[serde(serialize_with = "ordered_map")]
     pub instructions: HashMap<Instr, u64>,
 }
 
 
+pub struct Validation {
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl Validation {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+
 impl Params {
     pub fn for_testing() -> Params {
         Params {
             world_end: 3_000_000,
             log_interval: 100_000,
+            checkpoint_interval: 0,
+            census_size: 0,
 
             world_size: 150,
             start_pop_size: 500,
@@ -57,6 +104,15 @@ impl Params {
 
             ring_size: 3,
             ring_count: 2,
+            min_ring_count: None,
+            max_ring_count: None,
+            start_programs: Vec::new(),
+
+            crossover: Crossover::CutAndSplice,
+            point_mutation_rate: 0.0,
+            ring_duplication_rate: 0.0,
+            ring_deletion_rate: 0.0,
+            swap_mutation_rate: 0.0,
 
             instructions: Params::default_instr_map(),
         }
@@ -82,6 +138,10 @@ impl Params {
         instr_vec
     }
 
+    pub fn ring_count_range(&self) -> (usize, usize) {
+        (self.min_ring_count.unwrap_or(self.ring_count), self.max_ring_count.unwrap_or(self.ring_count))
+    }
+
     pub fn instr_cycles(&self, instr: &Instr) -> u64 {
         *self.instructions.get(instr).unwrap()
     }
@@ -90,5 +150,257 @@ impl Params {
         self.instructions.insert(instr, cycles);
     }
 
+    // Returns a copy of these params with the named fields replaced by the given values.
+    pub fn with_values(&self, values: &BTreeMap<String, Value>) -> Result<Params, Error> {
+        let mut p = serde_json::to_value(self).unwrap();
+        for (field, value) in values {
+            if p.get(field).is_none() {
+                return Err(Error::InvalidParams(vec![format!("unknown parameter {}", field)]));
+            }
+            p[field] = value.clone();
+        }
+        serde_json::from_value(p).map_err(|e| Error::InvalidParams(vec![e.to_string()]))
+    }
+
+
+    // Errors are parameter combinations that make the simulation panic or loop forever. Warnings
+    // are combinations that work technically but are unlikely to result in a useful simulation.
+
+    pub fn validate(&self) -> Validation {
+        let mut errors = Vec::new();
+        let mut warnings = Vec::new();
+
+        if self.world_size == 0 {
+            errors.push("world_size must be greater than 0".to_owned());
+        }
+        if self.log_interval == 0 {
+            errors.push("log_interval must be greater than 0".to_owned());
+        }
+        if self.ring_size == 0 {
+            errors.push("ring_size must be greater than 0".to_owned());
+        }
+        if self.ring_count == 0 {
+            errors.push("ring_count must be greater than 0".to_owned());
+        }
+        if self.min_ring_count == Some(0) {
+            errors.push("min_ring_count must be greater than 0".to_owned());
+        }
+        let (min_rings, max_rings) = self.ring_count_range();
+        // crossover needs at least two instructions to cut the programs, which can shrink to
+        // the minimum number of rings
+        let program_len = self.ring_size * min_rings;
+        if program_len == 1 {
+            errors.push(format!("programs must have at least 2 instructions, but ring_size * {} is {}",
+                                if self.min_ring_count.is_some() { "min_ring_count" } else { "ring_count" }, program_len));
+        }
+        if min_rings > self.ring_count || max_rings < self.ring_count {
+            errors.push(format!("ring_count ({}) must be between min_ring_count ({}) and max_ring_count ({})",
+                                self.ring_count, min_rings, max_rings));
+        }
+        for (i, text) in self.start_programs.iter().enumerate().filter(|_| self.ring_size > 0) {
+            match program::parse(text, self.ring_size) {
+                Ok(p) if p.len() / self.ring_size < min_rings || p.len() / self.ring_size > max_rings =>
+                    errors.push(format!("start_programs[{}] has {} rings, which is not between {} and {}",
+                                        i, p.len() / self.ring_size, min_rings, max_rings)),
+                Ok(p) => {
+                    if let Some(instr) = p.iter().find(|instr| !self.instructions.contains_key(instr)) {
+                        errors.push(format!("start_programs[{}] uses {:?}, which is not in instructions", i, instr));
+                    }
+                }
+                Err(e) => errors.push(format!("start_programs[{}]: {}", i, e)),
+            }
+        }
+        if self.instructions.is_empty() {
+            errors.push("instructions must not be empty".to_owned());
+        }
+        for instr in self.instr_list() {
+            if self.instr_cycles(instr) == 0 {
+                errors.push(format!("instruction {:?} must take at least 1 cycle", instr));
+            }
+        }
+        let rates = [("point_mutation_rate", self.point_mutation_rate), ("ring_duplication_rate", self.ring_duplication_rate),
+            ("ring_deletion_rate", self.ring_deletion_rate), ("swap_mutation_rate", self.swap_mutation_rate)];
+        for (name, rate) in rates.iter() {
+            if !(0.0..=1.0).contains(rate) {
+                errors.push(format!("{} ({}) must be in [0, 1]", name, rate));
+            }
+        }
+        if self.creature_start_ep == 0 {
+            errors.push("creature_start_ep must be greater than 0".to_owned());
+        }
+        if self.creature_start_ep > self.creature_max_ep {
+            errors.push(format!("creature_start_ep ({}) must not be greater than creature_max_ep ({})",
+                                self.creature_start_ep, self.creature_max_ep));
+        }
+        if self.eat_ep >= self.creature_max_ep {
+            errors.push(format!("eat_ep ({}) must be smaller than creature_max_ep ({})",
+                                self.eat_ep, self.creature_max_ep));
+        }
+        if !(self.plant_prob > 0.0 && self.plant_prob <= 1.0) {
+            errors.push(format!("plant_prob ({}) must be in (0, 1]", self.plant_prob));
+        }
+        if !(self.plant_prob_end > 0.0 && self.plant_prob_end <= 1.0) {
+            errors.push(format!("plant_prob_end ({}) must be in (0, 1]", self.plant_prob_end));
+        }
+
+        if self.min_mating_ep <= 2 * self.creature_start_ep {
+            warnings.push(format!("min_mating_ep ({}) should be greater than 2 * creature_start_ep ({})",
+                                  self.min_mating_ep, 2 * self.creature_start_ep));
+        }
+        if self.min_mating_ep >= self.creature_max_ep {
+            warnings.push(format!("min_mating_ep ({}) is not smaller than creature_max_ep ({}); creatures can never mate",
+                                  self.min_mating_ep, self.creature_max_ep));
+        }
+        if self.creature_max_age <= (self.creature_start_ep + self.eat_ep) as u64 {
+            warnings.push(format!("creature_max_age ({}) is too low for creatures to become adults",
+                                  self.creature_max_age));
+        }
+        if self.start_pop_size as u64 > (self.world_size as u64) * (self.world_size as u64) {
+            warnings.push(format!("start_pop_size ({}) is larger than the number of locations in the world",
+                                  self.start_pop_size));
+        }
+        if self.view_distance >= self.world_size {
+            warnings.push(format!("view_distance ({}) is not smaller than world_size ({})",
+                                  self.view_distance, self.world_size));
+        }
+        if self.log_interval > self.world_end {
+            warnings.push(format!("log_interval ({}) is greater than world_end ({})",
+                                  self.log_interval, self.world_end));
+        }
+        if (min_rings, max_rings) != (self.ring_count, self.ring_count)
+                && self.ring_duplication_rate == 0.0 && self.ring_deletion_rate == 0.0 {
+            warnings.push("min_ring_count and max_ring_count have no effect when ring_duplication_rate and ring_deletion_rate are 0"
+                .to_owned());
+        }
+
+        Validation { errors, warnings }
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testing_params_are_valid() {
+        assert!(Params::for_testing().validate().is_ok());
+    }
+
+    #[test]
+    fn reports_all_errors() {
+        let mut params = Params::for_testing();
+        params.ring_size = 0;
+        params.eat_ep = params.creature_max_ep;
+        params.set_instr_cycles(NOP, 0);
+
+        let v = params.validate();
+
+        assert_eq!(3, v.errors.len());
+        assert!(v.errors.iter().any(|e| e.starts_with("eat_ep")));
+    }
+
+    #[test]
+    fn programs_must_have_at_least_two_instructions() {
+        let mut params = Params::for_testing();
+        params.ring_size = 1;
+        params.ring_count = 1;
+
+        let v = params.validate();
+
+        assert_eq!(1, v.errors.len());
+        assert!(v.errors[0].starts_with("programs must have at least 2 instructions"));
+    }
+
+    #[test]
+    fn programs_must_not_shrink_below_two_instructions() {
+        let mut params = Params::for_testing();
+        params.ring_size = 1;
+        params.ring_count = 3;
+        params.min_ring_count = Some(1);
+
+        let v = params.validate();
+
+        assert_eq!(vec!["programs must have at least 2 instructions, but ring_size * min_ring_count is 1"], v.errors);
+    }
+
+    #[test]
+    fn ring_count_must_be_within_bounds() {
+        let mut params = Params::for_testing();
+        params.ring_duplication_rate = 0.01;
+        params.min_ring_count = Some(1);
+        params.max_ring_count = Some(4);
+        assert!(params.validate().is_ok());
+
+        params.max_ring_count = Some(1);
+        let v = params.validate();
+
+        assert_eq!(1, v.errors.len());
+        assert!(v.errors[0].starts_with("ring_count"));
+    }
+
+    #[test]
+    fn ring_count_bounds_without_ring_mutations_are_a_warning() {
+        let mut params = Params::for_testing();
+        params.max_ring_count = Some(4);
+        assert_eq!(1, params.validate().warnings.len());
+
+        params.ring_deletion_rate = 0.01;
+        assert!(params.validate().warnings.is_empty());
+    }
+
+    #[test]
+    fn start_programs_must_parse_and_have_the_right_number_of_rings() {
+        let mut params = Params::for_testing();
+        params.start_programs = vec!["MOV EAT MOV ; BFH NOP JMP ;".to_owned()];
+        assert!(params.validate().is_ok());
+
+        params.start_programs.push("MOV EAT MOV ;".to_owned());
+        params.start_programs.push("MOV EAT ;".to_owned());
+        let v = params.validate();
+
+        assert_eq!(vec!["start_programs[1] has 1 rings, which is not between 2 and 2",
+                        "start_programs[2]: line 1: ring 0 ends after 2 instructions instead of 3"], v.errors);
+    }
+
+    #[test]
+    fn start_programs_must_only_use_configured_instructions() {
+        let mut params = Params::for_testing();
+        params.start_programs = vec!["MOV EAT MOV ; BFH NOP JMP ;".to_owned()];
+        params.instructions.remove(&BFH);
+
+        assert_eq!(vec!["start_programs[0] uses BFH, which is not in instructions"], params.validate().errors);
+    }
+
+    #[test]
+    fn replaces_named_values() {
+        let mut values = BTreeMap::new();
+        values.insert("eat_ep".to_owned(), Value::from(123));
+
+        let p = Params::for_testing().with_values(&values).unwrap();
+
+        assert_eq!(123, p.eat_ep);
+        assert_eq!(Params::for_testing().creature_max_ep, p.creature_max_ep);
+    }
+
+    #[test]
+    fn rejects_unknown_names() {
+        let mut values = BTreeMap::new();
+        values.insert("eat".to_owned(), Value::from(123));
+
+        assert!(Params::for_testing().with_values(&values).is_err());
+    }
+
+    #[test]
+    fn low_mating_ep_is_a_warning() {
+        let mut params = Params::for_testing();
+        params.min_mating_ep = params.creature_start_ep;
+
+        let v = params.validate();
+
+        assert!(v.is_ok());
+        assert_eq!(1, v.warnings.len());
+    }
 }
 
@@ -1,4 +1,7 @@
 
+use serde_derive::*;
+
+#[derive(Serialize, Deserialize, Default)]
 pub struct Plant {
     pub ep: u32,
 }
@@ -1,6 +1,8 @@
 use std::iter;
 use serde_derive::*;
+use crate::error::Error;
 use crate::random::RNG;
+use self::Instr::*;
 
 #[derive(Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize, Debug, PartialOrd, Ord)]
 pub enum Instr {
@@ -15,9 +17,11 @@ pub enum Instr {
     BFA,
 }
 
+pub const ALL_INSTRS: [Instr; 9] = [NOP, TUR, TUL, MOV, EAT, JMP, JMZ, BFH, BFA];
+
 
 pub fn rand_program(instr_list: Vec<&Instr>, size:usize, rng: &mut RNG) -> Vec<Instr> {
-    iter::repeat_with(|| instr_list[rng.next_usize(instr_list.len())].clone())
+    iter::repeat_with(|| *instr_list[rng.next_usize(instr_list.len())])
         .take(size).collect()
 }
 
@@ -29,10 +33,120 @@ pub fn base_strategy(size: usize, rng: &mut RNG) -> Vec<Instr> {
 }
 
 
+
+// Programs as text. pp writes the instructions of a program with a `;` after each ring, which
+// is the format used in log entries and the web UI, and parse reads it back. When parsing, the
+// separators are optional, but where they are given they must be at the end of a ring. A ring
+// can also start with a label, `r1:` for the second ring, and `#` starts a comment that runs to
+// the end of the line. Instruction names are not case-sensitive.
+//
+// disassemble writes each ring on its own labelled line, with a comment on where the branches
+// in the ring go, so that a line-based diff shows which rings of two programs differ. Rings
+// that can't be reached from the first ring are marked. Its output can be parsed again.
+
+pub fn pp(program: &[Instr], ring_size: usize) -> String {
+    let mut out = String::new();
+    for (i, instr) in program.iter().enumerate() {
+        out.push_str(&format!("{:?} ", instr));
+        if (i + 1) % ring_size == 0 {
+            out.push_str("; ");
+        }
+    }
+    out
+}
+
+pub fn parse(text: &str, ring_size: usize) -> Result<Vec<Instr>, Error> {
+    let mut program = Vec::new();
+    let mut ring_start = 0;
+    let mut line_num = 1;
+    for (i, line) in text.lines().enumerate() {
+        line_num = i + 1;
+        let err = |message: String| Error::InvalidProgram { line: line_num, message };
+        let code = line.split('#').next().unwrap_or("").replace(';', " ; ");
+        for token in code.split_whitespace() {
+            let ring = program.len() / ring_size;
+            let pos = program.len() % ring_size;
+            if token == ";" {
+                if program.len() == ring_start {
+                    return Err(err("empty ring".to_owned()));
+                }
+                if pos != 0 {
+                    return Err(err(format!("ring {} ends after {} instructions instead of {}", ring, pos, ring_size)));
+                }
+                ring_start = program.len();
+            } else if let Some(label) = token.strip_suffix(':') {
+                if pos != 0 {
+                    return Err(err(format!("label {} is not at the start of a ring", token)));
+                }
+                if label != format!("r{}", ring) {
+                    return Err(err(format!("label {} is at the start of ring {}", token, ring)));
+                }
+            } else {
+                match ALL_INSTRS.iter().find(|i| format!("{:?}", i).eq_ignore_ascii_case(token)) {
+                    Some(instr) => program.push(*instr),
+                    None => return Err(err(format!("unknown instruction {}", token))),
+                }
+            }
+        }
+    }
+    let err = |message: String| Error::InvalidProgram { line: line_num, message };
+    if program.is_empty() {
+        return Err(err("the program has no instructions".to_owned()));
+    }
+    if program.len() % ring_size != 0 {
+        return Err(err(format!("the last ring has {} instructions instead of {}", program.len() % ring_size, ring_size)));
+    }
+    Ok(program)
+}
+
+pub fn disassemble(program: &[Instr], ring_size: usize) -> String {
+    let num_rings = program.len() / ring_size;
+    let width = format!("r{}:", num_rings.saturating_sub(1)).len();
+    let mut out = String::new();
+    let mut reachable = true;
+    for (r, ring) in program.chunks(ring_size).enumerate() {
+        let instrs: Vec<String> = ring.iter().map(|i| format!("{:?}", i)).collect();
+        out.push_str(&format!("{:<width$} {} ;", format!("r{}:", r), instrs.join(" "), width = width));
+        let branches = branches(ring, (r + 1) % num_rings);
+        let notes: Vec<String> = if reachable {
+            branches.iter().map(|(instr, target)| format!("{:?} -> r{}", instr, target)).collect()
+        } else {
+            vec!["unreachable".to_owned()]
+        };
+        if !notes.is_empty() {
+            out.push_str(&format!("  # {}", notes.join(", ")));
+        }
+        out.push('\n');
+        reachable = reachable && branches.iter().any(|(instr, _)| *instr != JMZ);
+    }
+    out
+}
+
+// The instructions of a ring that change the ring, and the rings they go to. Execution always
+// enters a ring at its start, so instructions after an unconditional jump are never executed.
+fn branches(ring: &[Instr], next_ring: usize) -> Vec<(Instr, usize)> {
+    let mut branches = Vec::new();
+    for instr in ring {
+        match instr {
+            BFH | BFA => branches.push((*instr, next_ring)),
+            JMP => {
+                branches.push((*instr, next_ring));
+                break;
+            }
+            JMZ => {
+                branches.push((*instr, 0));
+                break;
+            }
+            _ => (),
+        }
+    }
+    branches
+}
+
+
 #[cfg(test)]
 mod tests {
     use crate::params::Params;
-    use crate::program::Instr::*;
     use super::*;
 
     #[test]
@@ -81,4 +195,41 @@ mod tests {
 
         assert_ne!(prog0[0], prog1[0]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn parses_printed_program() {
+        let program = vec![MOV, EAT, MOV, BFH, NOP, JMP];
+
+        assert_eq!(program, parse(&pp(&program, 3), 3).unwrap());
+    }
+
+    #[test]
+    fn parses_labels_comments_and_lowercase_names() {
+        let text = "# eat and move\nr0: mov EAT MOV\nr1: BFH NOP JMP ;  # food here\n";
+
+        assert_eq!(vec![MOV, EAT, MOV, BFH, NOP, JMP], parse(text, 3).unwrap());
+    }
+
+    #[test]
+    fn rejects_programs_that_do_not_fit_the_ring_size() {
+        let message = |text, ring_size| parse(text, ring_size).unwrap_err().to_string();
+
+        assert_eq!("line 1: ring 0 ends after 2 instructions instead of 3", message("EAT MOV ; TUR", 3));
+        assert_eq!("line 2: the last ring has 1 instructions instead of 2", message("EAT MOV\nTUR", 2));
+        assert_eq!("line 1: unknown instruction FOO", message("EAT FOO", 2));
+        assert_eq!("line 1: label r2: is at the start of ring 1", message("EAT MOV ; r2: TUR NOP", 2));
+        assert_eq!("line 1: the program has no instructions", message("# nothing", 2));
+    }
+
+    #[test]
+    fn disassembly_labels_rings_and_annotates_branches() {
+        let program = vec![MOV, EAT, BFA, JMZ, NOP, JMP, TUR, TUR, TUR];
+
+        let text = disassemble(&program, 3);
+
+        assert_eq!("r0: MOV EAT BFA ;  # BFA -> r1\n\
+                    r1: JMZ NOP JMP ;  # JMZ -> r0\n\
+                    r2: TUR TUR TUR ;  # unreachable\n", text);
+        assert_eq!(program, parse(&text, 3).unwrap());
+    }
+}
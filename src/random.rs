@@ -1,18 +1,20 @@
 use rand::prelude::*;
 use rand_xorshift::XorShiftRng;
+use serde_derive::*;
 
 
+#[derive(Serialize, Deserialize)]
 pub struct RNG {
     seed: [u32; 4],
     system_rng: XorShiftRng,
+    #[serde(skip)]
     stubbed_seq: Option<Vec<u32>>,
 }
 
 
 impl RNG {
     pub fn new() -> RNG {
-        let mut rng = thread_rng();
-        RNG::from_seed(&[rng.next_u32(), rng.next_u32(), rng.next_u32(), rng.next_u32()])
+        RNG::from_seed(&RNG::new_seed())
     }
 
     pub fn from_seed(seed: &[u32; 4]) -> RNG {
@@ -25,7 +27,7 @@ impl RNG {
             }
         }
         RNG {
-            seed: seed.clone(),
+            seed: *seed,
             system_rng: XorShiftRng::from_seed(as_u8),
             stubbed_seq: None,
         }
@@ -35,6 +37,22 @@ impl RNG {
         &self.seed
     }
 
+    pub fn new_seed() -> [u32; 4] {
+        let mut rng = thread_rng();
+        [rng.next_u32(), rng.next_u32(), rng.next_u32(), rng.next_u32()]
+    }
+
+    // Derives the n-th seed from a master seed. Derived seeds are independent of each other
+    // for all practical purposes, and the same master seed always results in the same seeds.
+    pub fn derive_seed(master: &[u32; 4], n: u64) -> [u32; 4] {
+        let mut x = ((master[0] as u64) << 32 | master[1] as u64)
+            ^ ((master[2] as u64) << 32 | master[3] as u64).rotate_left(29);
+        x = x.wrapping_add(n.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        let a = splitmix64(&mut x);
+        let b = splitmix64(&mut x);
+        [(a >> 32) as u32, a as u32, (b >> 32) as u32, b as u32]
+    }
+
     pub fn next_u32(&mut self, ceiling: u32) -> u32 {
         if let Some(val) = self.get_next_value() {
             if val > ceiling {
@@ -73,6 +91,20 @@ impl RNG {
     }
 }
 
+fn splitmix64(x: &mut u64) -> u64 {
+    *x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *x;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+impl Default for RNG {
+    fn default() -> Self {
+        RNG::new()
+    }
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -115,6 +147,14 @@ mod tests {
         }
     }
 
+    #[test]
+    fn derived_seeds_are_reproducible_and_distinct() {
+        let master = [7, 8, 9, 10];
+        assert_eq!(RNG::derive_seed(&master, 3), RNG::derive_seed(&master, 3));
+        assert_ne!(RNG::derive_seed(&master, 3), RNG::derive_seed(&master, 4));
+        assert_ne!(RNG::derive_seed(&master, 3), RNG::derive_seed(&[7, 8, 9, 11], 3));
+    }
+
     #[test]
     fn different_seed_should_result_in_different_sequence() {
         let mut rng0 = RNG::from_seed(&[1, 2, 3, 4]);
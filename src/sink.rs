@@ -0,0 +1,212 @@
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use serde_derive::*;
+use crate::error::Error;
+use crate::log::LogEntry;
+use crate::program::Instr;
+use crate::program::Instr::*;
+use crate::utils::round;
+
+
+// Log sinks receive the log entries of a world once they are complete. The memory sink leaves
+// the entries in the log, which is written with the result. The ndjson and csv sinks append
+// each entry to a file next to the result as soon as it is complete, and only the current
+// entry stays in memory. The null sink drops the entries and turns off the instruction
+// counters, which is useful for measuring the speed of the simulation.
+
+pub trait LogSink: Send {
+    fn write_entry(&mut self, entry: &LogEntry) -> Result<(), Error>;
+
+    fn keeps_entries(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LogSinkKind {
+    #[default]
+    Memory,
+    Ndjson,
+    Csv,
+    Null,
+}
+
+impl LogSinkKind {
+    // the extension of the file the entries are written to, if any
+    pub fn extension(self) -> Option<&'static str> {
+        match self {
+            LogSinkKind::Memory | LogSinkKind::Null => None,
+            LogSinkKind::Ndjson => Some("ndjson"),
+            LogSinkKind::Csv => Some("csv"),
+        }
+    }
+}
+
+
+// Opens the sink. A world resumed from a checkpoint has the entries since the checkpoint in
+// memory; entries in the file from that cycle onwards were written after the checkpoint and
+// are removed. For a new world, which has no entries yet, the file is started from scratch.
+pub fn open(kind: LogSinkKind, path: Option<&str>, first_cycle: Option<u64>) -> Result<Box<dyn LogSink>, Error> {
+    Ok(match (kind, path) {
+        (LogSinkKind::Ndjson, Some(path)) =>
+            Box::new(FileSink { file: reopen(path, first_cycle, None, ndjson_cycle)?, path: path.to_owned(), format: ndjson_line }),
+        (LogSinkKind::Csv, Some(path)) =>
+            Box::new(FileSink { file: reopen(path, first_cycle, Some(csv_header()), csv_cycle)?, path: path.to_owned(), format: csv_line }),
+        (LogSinkKind::Null, _) => Box::new(NullSink),
+        _ => Box::new(MemorySink),
+    })
+}
+
+
+pub struct MemorySink;
+
+impl LogSink for MemorySink {
+    fn write_entry(&mut self, _entry: &LogEntry) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn keeps_entries(&self) -> bool {
+        true
+    }
+}
+
+
+pub struct NullSink;
+
+impl LogSink for NullSink {
+    fn write_entry(&mut self, _entry: &LogEntry) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+
+struct FileSink {
+    file: File,
+    path: String,
+    format: fn(&LogEntry) -> String,
+}
+
+impl LogSink for FileSink {
+    fn write_entry(&mut self, entry: &LogEntry) -> Result<(), Error> {
+        let line = (self.format)(entry);
+        self.file.write_all(line.as_bytes()).map_err(|e| Error::io(&self.path, e))
+    }
+}
+
+pub fn reopen(path: &str, first_cycle: Option<u64>, header: Option<String>,
+              cycle_of: fn(&str) -> Option<u64>) -> Result<File, Error> {
+    let mut kept = header.unwrap_or_default();
+    if let (Some(first_cycle), true) = (first_cycle, Path::new(path).exists()) {
+        let existing = fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        for line in existing.lines() {
+            if cycle_of(line).is_some_and(|c| c < first_cycle) {
+                kept.push_str(line);
+                kept.push('\n');
+            }
+        }
+    }
+    if let Some(dir) = Path::new(path).parent() {
+        fs::create_dir_all(dir).map_err(|e| Error::io(path, e))?;
+    }
+    fs::write(path, kept).map_err(|e| Error::io(path, e))?;
+    OpenOptions::new().append(true).open(path).map_err(|e| Error::io(path, e))
+}
+
+
+fn ndjson_line(entry: &LogEntry) -> String {
+    serde_json::to_string(entry).unwrap() + "\n"
+}
+
+fn ndjson_cycle(line: &str) -> Option<u64> {
+    serde_json::from_str::<serde_json::Value>(line).ok()?.get("cycle")?.as_u64()
+}
+
+const CSV_COLUMNS: [&str; 20] = ["cycle", "num_creatures", "num_plants", "plant_ep", "creature_ep",
+    "mean_age", "median_age", "max_age", "mean_generation", "max_generation", "mean_ring_count", "births", "deaths_starved",
+    "deaths_old_age", "mating_attempts", "mutations", "num_genotypes", "shannon", "simpson", "mean_edit_distance"];
+
+pub const CSV_INSTR_COLUMNS: [Instr; 9] = [EAT, MOV, TUR, TUL, NOP, JMP, JMZ, BFH, BFA];
+
+pub fn csv_header() -> String {
+    let columns: Vec<String> = CSV_COLUMNS.iter().map(|c| c.to_string())
+        .chain(CSV_INSTR_COLUMNS.iter().map(|i| format!("{:?}", i)))
+        .map(|c| format!("\"{}\"", c))
+        .collect();
+    columns.join(",") + "\n"
+}
+
+pub fn csv_line(entry: &LogEntry) -> String {
+    let diversity = entry.diversity();
+    let fields: Vec<String> = vec![
+        entry.cycle().to_string(),
+        opt(entry.num_creatures()),
+        opt(entry.num_plants()),
+        opt(entry.plant_ep()),
+        opt(entry.creature_ep()),
+        opt(entry.mean_age().map(|a| round(a, 1))),
+        opt(entry.median_age()),
+        opt(entry.max_age()),
+        opt(entry.mean_generation().map(|g| round(g, 2))),
+        opt(entry.max_generation()),
+        opt(entry.mean_ring_count().map(|r| round(r, 2))),
+        entry.births().to_string(),
+        entry.deaths_starved().to_string(),
+        entry.deaths_old_age().to_string(),
+        entry.mating_attempts().to_string(),
+        entry.mutations().to_string(),
+        opt(diversity.map(|d| d.num_genotypes)),
+        opt(diversity.map(|d| round(d.shannon, 4))),
+        opt(diversity.map(|d| round(d.simpson, 4))),
+        opt(diversity.and_then(|d| d.mean_edit_distance).map(|d| round(d, 2))),
+    ].into_iter()
+        .chain(CSV_INSTR_COLUMNS.iter().map(|i| entry.instr_count(*i).to_string()))
+        .collect();
+    fields.join(",") + "\n"
+}
+
+fn opt<T: ToString>(value: Option<T>) -> String {
+    value.map_or(String::new(), |v| v.to_string())
+}
+
+fn csv_cycle(line: &str) -> Option<u64> {
+    line.split(',').next()?.parse().ok()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::Log;
+
+    fn entries(cycles: &[u64]) -> Vec<LogEntry> {
+        let mut log = Log::new();
+        for c in cycles {
+            log.add_entry(*c);
+        }
+        log.take_all()
+    }
+
+    #[test]
+    fn reopening_removes_entries_from_the_first_cycle_in_memory_onwards() {
+        let path = std::env::temp_dir().join(format!("crellinor-sink-{}.csv", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let mut sink = open(LogSinkKind::Csv, Some(path), None).unwrap();
+        for e in entries(&[100, 200, 300]) {
+            sink.write_entry(&e).unwrap();
+        }
+        let mut sink = open(LogSinkKind::Csv, Some(path), Some(200)).unwrap();
+        for e in entries(&[200]) {
+            sink.write_entry(&e).unwrap();
+        }
+        let text = fs::read_to_string(path).unwrap();
+        let _ = fs::remove_file(path);
+
+        let cycles: Vec<&str> = text.lines().skip(1).map(|l| l.split(',').next().unwrap()).collect();
+        assert_eq!(vec!["100", "200"], cycles);
+        assert!(text.starts_with("\"cycle\",\"num_creatures\",\"num_plants\""));
+    }
+}
@@ -39,7 +39,7 @@ impl Terrain {
     pub fn all_creatures(&self) -> Vec<&Creature> {
         self.occupied
             .iter()
-            .filter_map(|&i| self.creature_at(self.idx_to_pos(i as usize)))
+            .filter_map(|&i| self.creature_at(self.idx_to_pos(i)))
             .collect()
     }
 
@@ -47,12 +47,16 @@ impl Terrain {
         self.occupied
             .iter()
             .filter_map(|&i| {
-                let p = self.idx_to_pos(i as usize);
+                let p = self.idx_to_pos(i);
                 self.creature_at(p).map(|c| (p, c))
             })
             .collect()
     }
 
+    pub fn all_plants(&self) -> Vec<&Plant> {
+        self.plants.iter().flatten().collect()
+    }
+
     pub fn all_plants_with_pos(&self) -> Vec<((u32, u32), &Plant)> {
         let mut out = Vec::new();
         for y in 0..self.size {
@@ -143,10 +147,7 @@ impl Terrain {
 
     pub fn take_plant_at(&mut self, pos: (u32, u32)) -> Option<Plant> {
         let idx = self.pos_to_idx(pos);
-        if self.plants[idx].is_some() {
-            return self.plants[idx].take();
-        }
-        return None;
+        self.plants[idx].take()
     }
 
 
@@ -275,7 +276,7 @@ mod tests {
         let mut list = t.all_plants_with_pos();
 
         assert_eq!(2, list.len());
-        list.sort_by(|a, b| a.1.ep.cmp(&b.1.ep) );
+        list.sort_by_key(|a| a.1.ep);
 
         assert_eq!(14, list[0].1.ep);
         assert_eq!((1, 4), list[0].0);
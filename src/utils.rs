@@ -1,4 +1,6 @@
+use std::collections::{BTreeMap, HashMap};
 use std::ops::Mul;
+use serde::{Serialize, Serializer};
 
 pub fn round(val: f64, p: i32) -> f64 {
     let f = 10.0_f64.powi(p);
@@ -8,4 +10,22 @@ pub fn round(val: f64, p: i32) -> f64 {
 pub fn square<T>(x: T) -> T
     where T: Copy + Mul<Output = T>  {
     x * x
-}
\ No newline at end of file
+}
+
+// FNV-1a, which unlike the hasher in the standard library is guaranteed to stay the same
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+// Serializes a hash map with its keys in order, which keeps output files byte-for-byte identical
+// between runs.
+pub fn ordered_map<S, K, V>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer, K: Ord + Serialize, V: Serialize {
+    let ordered: BTreeMap<&K, &V> = map.iter().collect();
+    ordered.serialize(serializer)
+}
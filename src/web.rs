@@ -16,9 +16,7 @@ use serde_derive::*;
 
 use crate::world::World;
 
-pub fn run(mut world: World, app_path: &str, addr: &str) {
-    println!("Setting up world");
-    world.add_initial_plants_and_creatures();
+pub fn run(world: World, app_path: &str, addr: &str) {
     println!("Listening for requests at http://{}", addr);
     let _ = gotham::start(addr.to_string(), router(app_path, WorldWrapper::new(world)));
 }
@@ -89,6 +87,7 @@ impl WorldWrapper {
 
         let creatures = world.terrain.all_creatures_with_pos().iter().map(|((x, y), creature)| {
             CreatureDoc {
+                id: creature.id, parents: creature.parents, generation: creature.generation,
                 x: *x, y: *y,
                 b: creature.bearing, ep: creature.ep, pc: creature.pc,
                 program: creature.pp_program(),
@@ -139,6 +138,9 @@ struct PlantDoc {
 
 #[derive(Serialize, Clone)]
 struct CreatureDoc {
+    id: u64,
+    parents: Option<(u64, u64)>,
+    generation: u32,
     x: u32,
     y: u32,
     b: u16,
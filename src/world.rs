@@ -1,49 +1,91 @@
 use std::cmp;
 use std::f64::consts::E;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Instant;
+use uuid::Uuid;
 
+use crate::campaign::SweepPoint;
 use crate::creature::Creature;
 use crate::creature::PContext;
+use crate::diversity::Diversity;
+use crate::error::Error;
+use crate::genealogy::{GenealogyFile, Record};
 use crate::params::Params;
 use crate::plant::Plant;
+use crate::program::Instr;
 use crate::random::RNG;
 use crate::terrain::Terrain;
-use crate::log::Log;
+use crate::log::{Death, Log};
+use crate::sink;
+use crate::sink::{LogSink, LogSinkKind, MemorySink};
+use crate::loader::Output;
 use crate::{loader, program};
 
 
 pub struct World {
+    pub id: String,
     pub name: Option<String>,
     pub params: Params,
     pub random: RNG,
     pub terrain: Terrain,
     pub cycle: u64,
+    pub next_creature_id: u64,
     pub log: Log,
+    pub checkpoint_at: Option<u64>,
+    pub sim_num: Option<u32>,
+    pub sweep_point: Option<SweepPoint>,
+    pub stop_flag: Option<Arc<AtomicBool>>,
+    pub output: Output,
+    pub sink: Box<dyn LogSink>,
+    pub genealogy: Option<GenealogyFile>,
 }
 
 impl World {
-    pub fn new(name: &str, params: Params) -> World {
+    pub fn new(name: &str, params: Params) -> Result<World, Error> {
+        let validation = params.validate();
+        if !validation.is_ok() {
+            return Err(Error::InvalidParams(validation.errors));
+        }
         let terrain = Terrain::with_size(params.world_size);
-        World {
+        Ok(World {
+            id: Uuid::new_v4().simple().to_string(),
             name: Some(name.to_owned()),
             params,
             random: RNG::new(),
             terrain,
             cycle: 0,
+            next_creature_id: 1,
             log: Log::new(),
-        }
+            checkpoint_at: None,
+            sim_num: None,
+            sweep_point: None,
+            stop_flag: None,
+            output: Output::default(),
+            sink: Box::new(MemorySink),
+            genealogy: None,
+        })
     }
 
     pub fn for_testing() -> World {
         let params = Params::for_testing();
         let terrain = Terrain::with_size(params.world_size);
         World {
+            id: Uuid::new_v4().simple().to_string(),
             name: None,
             params,
             random: RNG::new(),
             terrain,
             cycle: 0,
+            next_creature_id: 1,
             log: Log::new(),
+            checkpoint_at: None,
+            sim_num: None,
+            sweep_point: None,
+            stop_flag: None,
+            output: Output::default(),
+            sink: Box::new(MemorySink),
+            genealogy: None,
         }
     }
 
@@ -62,6 +104,16 @@ impl World {
         self.terrain.num_creatures()
     }
 
+    pub fn status(&self) -> &'static str {
+        if self.num_creatures() <= 1 {
+            "ENDAB"
+        } else if self.cycle < self.params.world_end {
+            "INTERRUPTED"
+        } else {
+            "ENDOK"
+        }
+    }
+
     pub fn add_plant(&mut self, mut p: Plant, pos: (u32, u32)) {
         p.ep = self.params.plant_start_ep;
         self.terrain.set_plant_at(Some(p), pos);
@@ -76,8 +128,15 @@ impl World {
 
     pub fn add_initial_plants_and_creatures(&mut self) {
         self.cycle = 10_000;
-        for _ in 0..self.params.start_pop_size {
-            self.add_start_creature()
+        let start_programs: Vec<Vec<Instr>> = self.params.start_programs.iter()
+            .map(|text| program::parse(text, self.params.ring_size).expect("start programs are validated"))
+            .collect();
+        for i in 0..self.params.start_pop_size as usize {
+            let prog = match start_programs.len() {
+                0 => self.random_start_program(),
+                n => start_programs[i % n].clone(),
+            };
+            self.add_start_creature(prog)
         }
         for _ in 0..self.params.start_plant_count {
             let ep = self.params.plant_start_ep;
@@ -85,12 +144,18 @@ impl World {
         }
     }
 
-    fn add_start_creature(&mut self) -> () {
+    fn random_start_program(&mut self) -> Vec<Instr> {
         let p = &self.params;
         let mut prog = Vec::new();
         prog.append(&mut program::base_strategy(p.ring_size, &mut self.random));
         prog.append(&mut program::rand_program(p.instr_list(), p.ring_size * (p.ring_count - 1), &mut self.random));
-        let mut creature = Creature::new(prog, p);
+        prog
+    }
+
+    fn add_start_creature(&mut self, prog: Vec<Instr>) {
+        let mut creature = Creature::new(prog, &self.params);
+        creature.id = self.next_creature_id;
+        self.next_creature_id += 1;
         creature.bcycle = self.random.next_u32(self.cycle as u32) as u64;
         creature.bearing = self.random.choose(&[0, 90, 180, 270]);
         if let Some(pos) = self.terrain.rand_free_pos(&mut self.random) {
@@ -139,16 +204,24 @@ impl World {
         let params = &self.params;
         let random = &mut self.random;
         let log = &mut self.log;
+        let next_id = &mut self.next_creature_id;
         self.terrain.do_with_creatures_mut(|terrain, creature, pos|
             {
                 creature.lastprocd = cycle;
                 creature.ep -= 1;
-                if (creature.age() >= params.creature_max_age) || (creature.ep == 0) {
+                if creature.age() >= params.creature_max_age {
+                    log.count_death(Death::OldAge);
+                    log.record(|| Record::death(creature, Death::OldAge));
+                    return None;
+                }
+                if creature.ep == 0 {
+                    log.count_death(Death::Starvation);
+                    log.record(|| Record::death(creature, Death::Starvation));
                     return None;
                 }
                 log.total_cycles += 1;
-                let mut ctx = PContext::new(params, log, random, terrain, cycle, pos);
-                return Some(creature.do_cycle(&mut ctx));
+                let mut ctx = PContext::new(params, log, random, terrain, next_id, cycle, pos);
+                Some(creature.do_cycle(&mut ctx))
             });
     }
 
@@ -161,14 +234,22 @@ impl World {
         }
     }
 
-    pub fn do_cycles_until_end(&mut self) {
-        self.log.add_entry(self.cycle);
-        self.log.set_num_creatures(self.num_creatures());
+    pub fn do_cycles_until_end(&mut self) -> Result<(), Error> {
+        if self.log.entries.is_empty() {
+            self.log.add_entry(self.cycle);
+            self.log_population();
+        }
         while self.num_creatures() > 1 && self.cycle < self.params.world_end {
-            let log_period = self.params.log_interval;
-            self.do_cycles(log_period);
+            // a world resumed from a checkpoint can be anywhere in a log period
+            let next_log_cycle = self.log.last_cycle().unwrap_or(self.cycle) + self.params.log_interval;
+            self.do_cycles_until(next_log_cycle)?;
+            if self.stop_requested() {
+                // leave the log as it is so that the world can be resumed from a checkpoint
+                return self.write_rolling_checkpoint();
+            }
             self.log.add_entry(self.cycle);
-            self.log.set_num_creatures(self.num_creatures());
+            self.log_population();
+            self.flush_log(false)?;
             if self.cycle >= self.params.world_end {
                 let adults: Vec<&Creature> = self.terrain.all_creatures().iter().filter(|c|
                     c.age() > (self.params.creature_start_ep + self.params.eat_ep) as u64
@@ -176,30 +257,126 @@ impl World {
                 self.log.set_programs(adults);
             }
         }
+        Ok(())
     }
 
 
-    // writing the result
+    fn log_population(&mut self) {
+        self.log.set_num_creatures(self.num_creatures());
+        self.log.set_population(&self.terrain.all_creatures(), &self.terrain.all_plants());
+        // like the instruction counters diversity and census aren't recorded when the log is
+        // muted; the pairs for the edit distance are sampled with their own generator to leave
+        // the world's sequence untouched
+        if !self.log.muted {
+            let creatures = self.terrain.all_creatures();
+            let programs: Vec<&[Instr]> = creatures.iter().map(|c| &c.program[..]).collect();
+            let mut rng = RNG::from_seed(&RNG::derive_seed(self.random.seed(), self.cycle));
+            self.log.set_diversity(Diversity::of(&programs, &mut rng));
+            if self.params.census_size > 0 {
+                self.log.set_census(&creatures, self.params.census_size);
+            }
+        }
+    }
 
-    pub fn write_result(&mut self) {
-        loader::write_world_with_log(&self)
+    fn do_cycles_until(&mut self, end: u64) -> Result<(), Error> {
+        while self.cycle < end && !self.stop_requested() {
+            self.do_one_cycle();
+            if self.checkpoint_at == Some(self.cycle) {
+                self.write_checkpoint()?;
+            }
+            let interval = self.params.checkpoint_interval;
+            if interval > 0 && self.cycle.is_multiple_of(interval) {
+                self.write_rolling_checkpoint()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn stop_requested(&self) -> bool {
+        self.stop_flag.as_ref().is_some_and(|f| f.load(Ordering::Relaxed))
+    }
+
+
+    // writing the result and checkpoints
+
+    // Hands the completed log entries, or all entries when the world has ended, to the sink.
+    fn flush_log(&mut self, all: bool) -> Result<(), Error> {
+        self.flush_genealogy()?;
+        if self.sink.keeps_entries() {
+            return Ok(());
+        }
+        let entries = if all { self.log.take_all() } else { self.log.take_completed() };
+        for entry in &entries {
+            self.sink.write_entry(entry)?;
+        }
+        Ok(())
+    }
+
+    fn flush_genealogy(&mut self) -> Result<(), Error> {
+        if let (Some(file), Some(records)) = (self.genealogy.as_mut(), self.log.genealogy.as_mut()) {
+            file.write(records)?;
+            records.clear();
+        }
+        Ok(())
+    }
+
+    pub fn write_result(&mut self) -> Result<(), Error> {
+        if self.status() != "INTERRUPTED" {
+            self.flush_log(true)?;
+        }
+        loader::write_result(self)?;
+        if self.status() != "INTERRUPTED" {
+            loader::remove_rolling_checkpoint(self);
+        }
+        Ok(())
+    }
+
+    // the genealogy must be complete up to the checkpoint, for a resumed world to continue it
+    pub fn write_checkpoint(&mut self) -> Result<(), Error> {
+        println!("Writing checkpoint at cycle {}.", self.cycle);
+        self.flush_genealogy()?;
+        loader::write_checkpoint(self)
+    }
+
+    fn write_rolling_checkpoint(&mut self) -> Result<(), Error> {
+        self.flush_genealogy()?;
+        loader::write_rolling_checkpoint(self)
     }
 
 
     // running the world
 
-    pub fn run(&mut self) {
+    pub fn run(&mut self) -> Result<(), Error> {
         self.add_initial_plants_and_creatures();
+        self.continue_run()
+    }
+
+    pub fn continue_run(&mut self) -> Result<(), Error> {
+        let kind = self.output.log_sink;
+        self.sink = sink::open(kind, loader::log_file_path(self).as_deref(), self.log.first_cycle())?;
+        self.log.muted = kind == LogSinkKind::Null;
+        if let Some(path) = loader::genealogy_file_path(self) {
+            // a new world has no log entries yet; its start creatures are the first records
+            let resumed_at = self.log.first_cycle().map(|_| self.cycle);
+            let mut file = GenealogyFile::open(&path, resumed_at)?;
+            if resumed_at.is_none() {
+                let births: Vec<Record> = self.terrain.all_creatures().iter().map(|c| Record::birth(c)).collect();
+                file.write(&births)?;
+            }
+            self.genealogy = Some(file);
+            self.log.genealogy = Some(Vec::new());
+        }
         let start = Instant::now();
-        self.do_cycles_until_end();
+        self.do_cycles_until_end()?;
         let end = Instant::now();
-        self.write_result();
+        self.write_result()?;
 
         let duration = end.duration_since(start);
         let millis = cmp::max(1, duration.as_secs() * 1000 + duration.subsec_millis() as u64);
         let cpm = self.log.total_cycles / millis;
         println!("Processed {}×10\u{2076} program cycles in {}s ({} cycles/ms).",
                  self.log.total_cycles/1_000_000, millis/1000, cpm);
+        Ok(())
     }
 
 }
@@ -239,6 +416,22 @@ mod tests {
         assert_eq!(90, w.creature_at((4, 3)).unwrap().bearing);
     }
 
+    #[test]
+    fn start_creatures_get_start_programs_in_turn() {
+        let mut w = World::for_testing();
+        w.params.start_pop_size = 3;
+        w.params.start_plant_count = 0;
+        w.params.start_programs = vec!["MOV EAT MOV ; BFH NOP JMP".to_owned(), "r0: TUR TUR TUR\nr1: EAT EAT EAT".to_owned()];
+
+        w.add_initial_plants_and_creatures();
+
+        let mut creatures = w.terrain.all_creatures();
+        creatures.sort_by_key(|c| c.id);
+        let programs: Vec<&[Instr]> = creatures.iter().map(|c| c.program.as_slice()).collect();
+        assert_eq!(vec![&[MOV, EAT, MOV, BFH, NOP, JMP][..], &[TUR, TUR, TUR, EAT, EAT, EAT], &[MOV, EAT, MOV, BFH, NOP, JMP]],
+                   programs);
+    }
+
     #[test]
     fn plant_reduction() {
         let mut w = World::for_testing();
@@ -1,6 +1,15 @@
+#![allow(clippy::bool_assert_comparison)]
+
 extern crate crellinor;
 
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use serde_json::{json, Value};
+use crellinor::Command;
 use crellinor::creature::Creature;
+use crellinor::loader;
+use crellinor::loader::OutputOptions;
 use crellinor::plant::Plant;
 use crellinor::program::Instr::*;
 use crellinor::world::World;
@@ -392,3 +401,43 @@ pub fn cycle_count(params: &Params, prog: &[Instr]) -> u64 {
     prog.iter().map(|instr| params.instr_cycles(instr)).sum()
 }
 
+
+// -- resuming unfinished runs
+
+#[test]
+fn run_only_resumes_checkpoint_of_same_seed_and_params() {
+    let dir = std::env::temp_dir().join(format!("crellinor-test-resume-{}", std::process::id()));
+    let root = dir.join("output").to_str().unwrap().to_owned();
+    fs::create_dir_all(&dir).unwrap();
+    let worldfile = dir.join("small.json").to_str().unwrap().to_owned();
+    let mut params = Params::for_testing();
+    params.world_size = 30;
+    params.start_pop_size = 20;
+    params.start_plant_count = 100;
+    params.world_end = 20_000;
+    params.log_interval = 5_000;
+    let doc = json!({ "format_version": loader::FORMAT_VERSION, "params": params, "seed": [1, 2, 3, 4] });
+    fs::write(&worldfile, doc.to_string()).unwrap();
+    // an unfinished run of the world file
+    let mut w = loader::load_world(&worldfile).unwrap();
+    w.output.root = root.clone();
+    w.add_initial_plants_and_creatures();
+    w.do_cycles(100);
+    loader::write_rolling_checkpoint(&w).unwrap();
+    let resumed_result = format!("{}/small/log-{}.json", root, w.id);
+    let run = |overrides: BTreeMap<String, Value>| crellinor::run(Command::Run {
+        worldfile: worldfile.clone(), seed: None, overrides, checkpoint_at: None,
+        output: OutputOptions { root: Some(root.clone()), ..OutputOptions::default() },
+    }).unwrap();
+
+    let mut overrides = BTreeMap::new();
+    overrides.insert("eat_ep".to_owned(), Value::from(params.eat_ep + 1));
+    run(overrides);
+    let resumed_with_overrides = Path::new(&resumed_result).exists();
+    run(BTreeMap::new());
+    let resumed_without_overrides = Path::new(&resumed_result).exists();
+    let checkpoints_left = loader::find_rolling_checkpoints(&w.output, "small").len();
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!((false, true, 0), (resumed_with_overrides, resumed_without_overrides, checkpoints_left));
+}